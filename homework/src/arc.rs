@@ -2,20 +2,32 @@
 //!
 //! See the `Arc` documentation for more details and specification.
 
+use std::alloc::{alloc, dealloc, handle_alloc_error, Layout};
 use std::fmt;
 use std::marker::PhantomData;
-use std::mem;
+use std::mem::{self, ManuallyDrop, MaybeUninit};
 use std::ops::Deref;
-use std::ptr::NonNull;
+use std::pin::Pin;
+use std::ptr::{self, NonNull};
 
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{AtomicUsize, Ordering};
+use loom::sync::atomic::{fence, AtomicUsize, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::thread::yield_now;
+#[cfg(not(feature = "check-loom"))]
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
 #[cfg(not(feature = "check-loom"))]
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::yield_now;
+
+mod atomic;
+mod biased;
+
+pub use atomic::AtomicArc;
+pub use biased::BiasedArc;
 
 const MAX_REFCOUNT: usize = (isize::MAX) as usize;
 
-/// Simplified `Arc` without `Weak` support.
+/// Simplified `Arc`.
 ///
 /// The main correctness guarantee of `Arc` is that the deallocation of its data and counter field
 /// happens-after all accesses to those fields.  An access (by `Deref::deref`, `get_mut`, ...) to an
@@ -33,17 +45,31 @@ const MAX_REFCOUNT: usize = (isize::MAX) as usize;
 /// `try_unwrap` also provides a similar guarantee as it returns the exclusive ownership of the
 /// data.
 ///
+/// A `Weak` pointer doesn't keep the data alive, but keeps the allocation alive so that it can try
+/// to `upgrade` to an `Arc`. So there are two counters: `strong` for the `Arc`s and `weak` for the
+/// `Weak`s plus one for all the `Arc`s together. The data is dropped when `strong` reaches zero,
+/// and the allocation is freed when `weak` reaches zero. Since a `Weak` may create a new `Arc` at
+/// any time, `get_mut` and `make_mut` have to make sure that there are no `Weak`s as well, and
+/// `try_unwrap` has to prevent the `Weak`s from upgrading.
+///
 /// The above explanation is based on the paper [RustBelt Meets Relaxed Memory by Dang et
 /// al.](https://plv.mpi-sws.org/rustbelt/rbrlx/).
-pub struct Arc<T> {
+///
+/// `T` may be unsized, e.g., `Arc<[T]>`, `Arc<str>` and `Arc<dyn Trait>`. Such an `Arc` is
+/// constructed by the `From` conversions, which move the data into the same allocation as the
+/// counters.
+pub struct Arc<T: ?Sized> {
     ptr: NonNull<ArcInner<T>>,
     phantom: PhantomData<ArcInner<T>>,
 }
 
-unsafe impl<T: Sync + Send> Send for Arc<T> {}
-unsafe impl<T: Sync + Send> Sync for Arc<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Send for Arc<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for Arc<T> {}
 
-impl<T> Arc<T> {
+// The data is never moved while there are `Arc`s, so `Arc` doesn't need to be pinned itself.
+impl<T: ?Sized> Unpin for Arc<T> {}
+
+impl<T: ?Sized> Arc<T> {
     fn from_inner(ptr: NonNull<ArcInner<T>>) -> Self {
         Self {
             ptr,
@@ -52,27 +78,259 @@ impl<T> Arc<T> {
     }
 }
 
-struct ArcInner<T> {
-    count: AtomicUsize,
+// `repr(C)` fixes the offset of `data`, so that the layout of `ArcInner<T>` for unsized `T` can be
+// computed from the layout of `data`.
+#[repr(C)]
+struct ArcInner<T: ?Sized> {
+    /// The number of `Arc`s.
+    strong: AtomicUsize,
+    /// The number of `Weak`s, plus one if there are any `Arc`s. Set to `usize::MAX` while `get_mut`
+    /// checks the uniqueness, to prevent `downgrade`s in the meantime.
+    weak: AtomicUsize,
+    /// The layout of the allocation, so that the last `Weak` frees it without referencing the
+    /// dropped data.
+    layout: Layout,
     data: T,
 }
 
-unsafe impl<T: Sync + Send> Send for ArcInner<T> {}
-unsafe impl<T: Sync + Send> Sync for ArcInner<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Send for ArcInner<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for ArcInner<T> {}
 
 impl<T> Arc<T> {
     /// Constructs a new `Arc<T>`.
     #[inline]
     pub fn new(data: T) -> Arc<T> {
         let x = Box::new(ArcInner {
-            count: AtomicUsize::new(1),
+            strong: AtomicUsize::new(1),
+            weak: AtomicUsize::new(1),
+            layout: Layout::new::<ArcInner<T>>(),
             data,
         });
         Self::from_inner(Box::leak(x).into())
     }
 
+    /// Constructs a new `Arc<T>` using a `Weak` pointer to itself, which is useful for a value
+    /// that refers to itself.
+    ///
+    /// Calling `upgrade` on the `Weak` inside `data_fn` gives `None`, since the value is not
+    /// constructed yet. The `Weak` may be cloned and stored in the value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::{Arc, Weak};
+    ///
+    /// struct Gadget {
+    ///     me: Weak<Gadget>,
+    /// }
+    ///
+    /// let gadget = Arc::new_cyclic(|me| {
+    ///     assert!(me.upgrade().is_none());
+    ///     Gadget { me: me.clone() }
+    /// });
+    /// assert!(Arc::ptr_eq(&gadget, &gadget.me.upgrade().unwrap()));
+    /// ```
+    pub fn new_cyclic<F: FnOnce(&Weak<T>) -> T>(data_fn: F) -> Arc<T> {
+        // The strong count is zero, so the `Weak`s can't upgrade until the data is initialized.
+        // `ArcInner<MaybeUninit<T>>` has the same layout as `ArcInner<T>`.
+        let uninit = Box::new(ArcInner {
+            strong: AtomicUsize::new(0),
+            weak: AtomicUsize::new(1),
+            layout: Layout::new::<ArcInner<T>>(),
+            data: MaybeUninit::<T>::uninit(),
+        });
+        let ptr = NonNull::from(Box::leak(uninit)).cast::<ArcInner<T>>();
+        // If `data_fn` panics, dropping `weak` frees the allocation.
+        let weak = Weak { ptr };
+        let data = data_fn(&weak);
+
+        unsafe {
+            ptr::write(&mut (*ptr.as_ptr()).data, data);
+            // Release synchronizes with the acquire in `upgrade`, so that the initialization
+            // happens-before the accesses by the upgraded `Arc`s.
+            (*ptr.as_ptr()).strong.store(1, Ordering::Release);
+        }
+        // The weak count of `weak` becomes the implicit weak reference held by the `Arc`s.
+        mem::forget(weak);
+        Self::from_inner(ptr)
+    }
+
+    /// Constructs a new `Pin<Arc<T>>`.
+    ///
+    /// The data is never moved, since moving it out (by `try_unwrap`, `make_mut`, ...) requires
+    /// an `Arc` by value or `&mut Arc`, which `Pin` doesn't expose.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let pinned = Arc::pin(5);
+    /// let other = pinned.clone();
+    /// assert_eq!(*other, 5);
+    /// ```
+    pub fn pin(data: T) -> Pin<Arc<T>> {
+        unsafe { Pin::new_unchecked(Arc::new(data)) }
+    }
+
+    /// Returns the inner value, if the given `Arc` is unique.
+    ///
+    /// Otherwise, an `Err` is returned with the same `Arc` that was passed in. The outstanding
+    /// `Weak`s don't prevent unwrapping, and they fail to `upgrade` afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let x = Arc::new(3);
+    /// assert_eq!(Arc::try_unwrap(x).unwrap(), 3);
+    ///
+    /// let x = Arc::new(4);
+    /// let _y = Arc::clone(&x);
+    /// assert_eq!(*Arc::try_unwrap(x).unwrap_err(), 4);
+    /// ```
+    #[inline]
+    pub fn try_unwrap(this: Self) -> Result<T, Self> {
+        // Setting the strong count to zero prevents the `Weak`s from upgrading.
+        if this
+            .inner()
+            .strong
+            .compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(this);
+        }
+
+        let data = unsafe { ptr::read(&this.inner().data) };
+        // Release the implicit weak reference held by the `Arc`s.
+        drop(Weak { ptr: this.ptr });
+        mem::forget(this);
+        Ok(data)
+    }
+}
+
+impl<T: ?Sized> Arc<T> {
+    /// Allocates an `ArcInner<T>` with uninitialized data of `data_layout`. `into_inner` converts
+    /// the pointer to the allocation into a pointer to `ArcInner<T>` with the metadata of `T`. The
+    /// counters are initialized as a new `Arc`.
+    unsafe fn allocate_for_layout(
+        data_layout: Layout,
+        into_inner: impl FnOnce(*mut u8) -> *mut ArcInner<T>,
+    ) -> *mut ArcInner<T> {
+        let layout = Layout::new::<ArcInner<()>>()
+            .extend(data_layout)
+            .unwrap()
+            .0
+            .pad_to_align();
+        let mem = alloc(layout);
+        if mem.is_null() {
+            handle_alloc_error(layout);
+        }
+
+        let inner = into_inner(mem);
+        ptr::write(&mut (*inner).strong, AtomicUsize::new(1));
+        ptr::write(&mut (*inner).weak, AtomicUsize::new(1));
+        ptr::write(&mut (*inner).layout, layout);
+        inner
+    }
+
+    /// Allocates an `ArcInner<T>` with uninitialized data of the same layout and metadata as
+    /// `ptr`.
+    unsafe fn allocate_for_ptr(ptr: *const T) -> *mut ArcInner<T> {
+        Self::allocate_for_layout(Layout::for_value(&*ptr), |mem| {
+            set_data_ptr(ptr as *mut T, mem) as *mut ArcInner<T>
+        })
+    }
+
+    /// Moves the boxed value into a new `Arc`.
+    fn from_box(src: Box<T>) -> Arc<T> {
+        unsafe {
+            let value_size = mem::size_of_val(&*src);
+            let ptr = Self::allocate_for_ptr(&*src);
+            ptr::copy_nonoverlapping(
+                &*src as *const T as *const u8,
+                &mut (*ptr).data as *mut T as *mut u8,
+                value_size,
+            );
+            // Free the allocation of the box without dropping the moved value.
+            let src = Box::from_raw(Box::into_raw(src) as *mut ManuallyDrop<T>);
+            drop(src);
+            Self::from_inner(NonNull::new_unchecked(ptr))
+        }
+    }
+
+    /// Creates a new `Weak` pointer to this allocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let five = Arc::new(5);
+    /// let weak_five = Arc::downgrade(&five);
+    ///
+    /// assert_eq!(*weak_five.upgrade().unwrap(), 5);
+    /// ```
+    pub fn downgrade(this: &Self) -> Weak<T> {
+        let mut cur = this.inner().weak.load(Ordering::Relaxed);
+        loop {
+            // `get_mut` is checking the uniqueness, which takes a short time.
+            if cur == usize::MAX {
+                yield_now();
+                cur = this.inner().weak.load(Ordering::Relaxed);
+                continue;
+            }
+            if cur == MAX_REFCOUNT {
+                panic!();
+            }
+            // Acquire synchronizes with the release in `is_unique`, so that the `Weak` is created
+            // after the exclusive borrow ends.
+            match this.inner().weak.compare_exchange_weak(
+                cur,
+                cur + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Weak { ptr: this.ptr },
+                Err(old) => cur = old,
+            }
+        }
+    }
+
+    /// Gets the number of `Weak` pointers to this allocation.
+    ///
+    /// # Safety
+    ///
+    /// This method by itself is safe, but using it correctly requires extra care.
+    /// Another thread can change the weak count at any time,
+    /// including potentially between calling this method and acting on the result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let five = Arc::new(5);
+    /// let _weak_five = Arc::downgrade(&five);
+    ///
+    /// // This assertion is deterministic because we haven't shared
+    /// // the `Arc` or `Weak` between threads.
+    /// assert_eq!(1, Arc::weak_count(&five));
+    /// ```
+    #[inline]
+    pub fn weak_count(this: &Self) -> usize {
+        let count = this.inner().weak.load(Ordering::Acquire);
+        // If the weak count is locked, the count was 1 just before the lock, i.e., no `Weak`s.
+        if count == usize::MAX {
+            0
+        } else {
+            count - 1
+        }
+    }
+
     /// Returns a mutable reference into the given `Arc` if there are
-    /// no other `Arc`. Otherwise, return `None`.
+    /// no other `Arc` or `Weak` pointers. Otherwise, return `None`.
     ///
     /// # Examples
     ///
@@ -88,6 +346,12 @@ impl<T> Arc<T> {
     ///
     /// drop(y);
     /// assert!(Arc::get_mut(&mut x).is_some());
+    ///
+    /// let z = Arc::downgrade(&x);
+    /// assert!(Arc::get_mut(&mut x).is_none());
+    ///
+    /// drop(z);
+    /// assert!(Arc::get_mut(&mut x).is_some());
     /// ```
     #[inline]
     pub fn get_mut(this: &mut Self) -> Option<&mut T> {
@@ -98,11 +362,27 @@ impl<T> Arc<T> {
         }
     }
 
-    // Used in `get_mut` and `make_mut` to check if the given `Arc` is the unique reference to the
-    // underlying data.
+    // Used in `get_mut` to check if the given `Arc` is the unique reference to the underlying data,
+    // including `Weak`s.
     #[inline]
     fn is_unique(&mut self) -> bool {
-        Self::count(self) == 1
+        // Lock the weak count if there are no `Weak`s, so that no `Weak` is created by another
+        // `Arc` until the strong count is checked. Acquire synchronizes with the release of the last
+        // `Weak`'s drop.
+        if self
+            .inner()
+            .weak
+            .compare_exchange(1, usize::MAX, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            // Acquire synchronizes with the release of the other `Arc`s' drop.
+            let unique = self.inner().strong.load(Ordering::Acquire) == 1;
+            // Release synchronizes with the acquire in `downgrade`.
+            self.inner().weak.store(1, Ordering::Release);
+            unique
+        } else {
+            false
+        }
     }
 
     /// Returns a mutable reference into the given `Arc` without any check.
@@ -153,7 +433,7 @@ impl<T> Arc<T> {
     /// ```
     #[inline]
     pub fn count(this: &Self) -> usize {
-        this.inner().count.load(Ordering::Acquire)
+        this.inner().strong.load(Ordering::Acquire)
     }
 
     #[inline]
@@ -183,37 +463,74 @@ impl<T> Arc<T> {
     /// ```
     #[inline]
     pub fn ptr_eq(this: &Self, other: &Self) -> bool {
-        this.ptr.as_ptr() == other.ptr.as_ptr()
+        // Compare the addresses only, since the vtables of the same type may differ.
+        this.ptr.as_ptr() as *const u8 == other.ptr.as_ptr() as *const u8
     }
+}
 
-    /// Returns the inner value, if the given `Arc` is unique.
-    ///
-    /// Otherwise, an `Err` is returned with the same `Arc` that was passed in.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use cs492_concur_homework::Arc;
+impl<T> Arc<[T]> {
+    /// Allocates an `ArcInner<[T]>` with `len` uninitialized elements.
+    unsafe fn allocate_for_slice(len: usize) -> *mut ArcInner<[T]> {
+        Self::allocate_for_layout(Layout::array::<T>(len).unwrap(), |mem| {
+            ptr::slice_from_raw_parts_mut(mem as *mut T, len) as *mut ArcInner<[T]>
+        })
+    }
+
+    /// Moves the elements of `src` into a new `Arc<[T]>`, leaving `src` empty.
+    fn from_vec(mut src: Vec<T>) -> Arc<[T]> {
+        unsafe {
+            let ptr = Self::allocate_for_slice(src.len());
+            ptr::copy_nonoverlapping(
+                src.as_ptr(),
+                &mut (*ptr).data as *mut [T] as *mut T,
+                src.len(),
+            );
+            // The elements are moved, so only the buffer of `src` is freed.
+            src.set_len(0);
+            Self::from_inner(NonNull::new_unchecked(ptr))
+        }
+    }
+}
+
+impl<T: Copy> Arc<[T]> {
+    /// Copies the elements of `src` into a new `Arc<[T]>`.
+    fn from_slice(src: &[T]) -> Arc<[T]> {
+        unsafe {
+            let ptr = Self::allocate_for_slice(src.len());
+            ptr::copy_nonoverlapping(
+                src.as_ptr(),
+                &mut (*ptr).data as *mut [T] as *mut T,
+                src.len(),
+            );
+            Self::from_inner(NonNull::new_unchecked(ptr))
+        }
+    }
+}
+
+impl Arc<[u8]> {
+    /// Converts the bytes into an `Arc<str>` in place.
     ///
-    /// let x = Arc::new(3);
-    /// assert_eq!(Arc::try_unwrap(x).unwrap(), 3);
+    /// # Safety
     ///
-    /// let x = Arc::new(4);
-    /// let _y = Arc::clone(&x);
-    /// assert_eq!(*Arc::try_unwrap(x).unwrap_err(), 4);
-    /// ```
-    #[inline]
-    pub fn try_unwrap(this: Self) -> Result<T, Self> {
-        if Self::count(&this) == 1 {
-            let data = unsafe { Box::from_raw(this.ptr.as_ptr()).data };
-            mem::forget(this);
-            Ok(data)
-        } else {
-            Err(this)
-        }
+    /// The bytes should be valid UTF-8.
+    unsafe fn into_str(self) -> Arc<str> {
+        // `str` has the same layout as `[u8]`.
+        let ptr = self.ptr.as_ptr() as *mut ArcInner<str>;
+        mem::forget(self);
+        Arc::from_inner(NonNull::new_unchecked(ptr))
     }
 }
 
+/// Sets the data pointer of a possibly fat pointer `ptr` to `data`, keeping the metadata of `ptr`.
+///
+/// # Safety
+///
+/// Relies on the data pointer being the first word of a fat pointer.
+unsafe fn set_data_ptr<T: ?Sized, U>(mut ptr: *mut T, data: *mut U) -> *mut T {
+    ptr::write(&mut ptr as *mut *mut T as *mut *mut u8, data as *mut u8);
+    ptr
+}
+
 impl<T: Clone> Arc<T> {
     /// Makes a mutable reference into the given `Arc`.
     ///
@@ -221,6 +538,9 @@ impl<T: Clone> Arc<T> {
     /// allocation and invoke `clone` on the inner value to ensure unique ownership. This is also
     /// referred to as clone-on-write.
     ///
+    /// If there are no other `Arc` but some `Weak` pointers, then the inner value is moved to a new
+    /// allocation, and the `Weak`s are disassociated from it.
+    ///
     /// See also `get_mut`, which will fail rather than cloning.
     ///
     /// # Examples
@@ -242,19 +562,32 @@ impl<T: Clone> Arc<T> {
     /// ```
     #[inline]
     pub fn make_mut(this: &mut Self) -> &mut T {
-        if this.is_unique() == false {
-            this.inner().count.fetch_sub(1, Ordering::Relaxed);
-            let x = Box::new(ArcInner {
-                count: AtomicUsize::new(1),
-                data: this.inner().data.clone(),
-            });
-            this.ptr = Box::leak(x).into();
+        // Temporarily setting the strong count to zero prevents the `Weak`s from upgrading, and
+        // acquire synchronizes with the release of the other `Arc`s' drop.
+        if this
+            .inner()
+            .strong
+            .compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // There are other `Arc`s, so clone the data.
+            *this = Arc::new(this.inner().data.clone());
+        } else if this.inner().weak.load(Ordering::Relaxed) != 1 {
+            // There are `Weak`s only, so move the data and leave the old allocation to the `Weak`s.
+            // The strong count stays zero, so they can't upgrade any more.
+            let data = unsafe { ptr::read(&this.inner().data) };
+            let weak = Weak { ptr: this.ptr };
+            unsafe { ptr::write(this, Arc::new(data)) };
+            drop(weak);
+        } else {
+            // This is the unique reference, so restore the strong count.
+            this.inner().strong.store(1, Ordering::Release);
         }
         unsafe { Self::get_mut_unchecked(this) }
     }
 }
 
-impl<T> Clone for Arc<T> {
+impl<T: ?Sized> Clone for Arc<T> {
     /// Makes a clone of the `Arc` pointer.
     ///
     /// This creates another pointer to the same allocation, increasing the
@@ -275,7 +608,7 @@ impl<T> Clone for Arc<T> {
     /// ```
     #[inline]
     fn clone(&self) -> Arc<T> {
-        let count = self.inner().count.fetch_add(1, Ordering::Relaxed);
+        let count = self.inner().strong.fetch_add(1, Ordering::Relaxed);
         if count != MAX_REFCOUNT {
             Self::from_inner(self.ptr)
         } else {
@@ -284,7 +617,7 @@ impl<T> Clone for Arc<T> {
     }
 }
 
-impl<T> Deref for Arc<T> {
+impl<T: ?Sized> Deref for Arc<T> {
     type Target = T;
 
     #[inline]
@@ -293,11 +626,12 @@ impl<T> Deref for Arc<T> {
     }
 }
 
-impl<T> Drop for Arc<T> {
+impl<T: ?Sized> Drop for Arc<T> {
     /// Drops the `Arc`.
     ///
     /// This will decrement the reference count. If the reference
-    /// count reaches zero, we `drop` the inner value.
+    /// count reaches zero, we `drop` the inner value. The allocation is freed when there are no
+    /// `Weak` pointers as well.
     ///
     /// # Examples
     ///
@@ -319,27 +653,273 @@ impl<T> Drop for Arc<T> {
     /// drop(foo2);   // Prints "dropped!"
     /// ```
     fn drop(&mut self) {
-        if self.inner().count.fetch_sub(1, Ordering::AcqRel) == 1 {
-            let inner = unsafe { Box::from_raw(self.ptr.as_ptr()) };
-            drop(inner);
+        if self.inner().strong.fetch_sub(1, Ordering::AcqRel) == 1 {
+            unsafe { ptr::drop_in_place(Self::get_mut_unchecked(self)) };
+            // Release the implicit weak reference held by the `Arc`s.
+            drop(Weak { ptr: self.ptr });
         }
     }
 }
 
-impl<T: fmt::Display> fmt::Display for Arc<T> {
+impl<T: ?Sized + fmt::Display> fmt::Display for Arc<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Display::fmt(&**self, f)
     }
 }
 
-impl<T: fmt::Debug> fmt::Debug for Arc<T> {
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Arc<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Debug::fmt(&**self, f)
     }
 }
 
-impl<T> fmt::Pointer for Arc<T> {
+impl<T: ?Sized> fmt::Pointer for Arc<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Pointer::fmt(&(&**self as *const T), f)
     }
 }
+
+impl<T> From<T> for Arc<T> {
+    /// Moves `t` into a new `Arc`.
+    fn from(t: T) -> Self {
+        Arc::new(t)
+    }
+}
+
+impl<T: ?Sized> From<Box<T>> for Arc<T> {
+    /// Moves the boxed value into a new `Arc`, e.g., to convert `Box<dyn Trait>` into
+    /// `Arc<dyn Trait>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fmt::Display;
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let boxed: Box<dyn Display + Send + Sync> = Box::new(5);
+    /// let shared: Arc<dyn Display + Send + Sync> = Arc::from(boxed);
+    /// assert_eq!(shared.to_string(), "5");
+    /// ```
+    fn from(src: Box<T>) -> Self {
+        Arc::from_box(src)
+    }
+}
+
+impl<T> From<Vec<T>> for Arc<[T]> {
+    /// Moves the elements of the vector into a new `Arc<[T]>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let shared: Arc<[i32]> = Arc::from(vec![1, 2, 3]);
+    /// assert_eq!(&shared[..], [1, 2, 3]);
+    /// ```
+    fn from(v: Vec<T>) -> Self {
+        Arc::from_vec(v)
+    }
+}
+
+impl From<&str> for Arc<str> {
+    /// Copies the string into a new `Arc<str>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let shared: Arc<str> = Arc::from("eggplant");
+    /// assert_eq!("eggplant", &shared[..]);
+    /// ```
+    fn from(v: &str) -> Self {
+        // The bytes of a `str` are valid UTF-8.
+        unsafe { Arc::<[u8]>::from_slice(v.as_bytes()).into_str() }
+    }
+}
+
+impl From<String> for Arc<str> {
+    /// Moves the bytes of the string into a new `Arc<str>`.
+    fn from(v: String) -> Self {
+        // The bytes of a `String` are valid UTF-8.
+        unsafe { Arc::<[u8]>::from_vec(v.into_bytes()).into_str() }
+    }
+}
+
+/// `Weak` is a version of `Arc` that holds a non-owning reference to the managed allocation. The
+/// allocation is accessed by calling `upgrade` on the `Weak` pointer, which returns an
+/// `Option<Arc<T>>`.
+///
+/// Since a `Weak` reference does not count towards ownership, it will not prevent the value stored
+/// in the allocation from being dropped, and `Weak` itself makes no guarantees about the value
+/// still being present. Thus it may return `None` when `upgrade`d. Note however that a `Weak`
+/// reference does prevent the allocation itself (the backing store) from being deallocated.
+///
+/// # Examples
+///
+/// ```
+/// use cs492_concur_homework::Arc;
+///
+/// let five = Arc::new(5);
+/// let weak_five = Arc::downgrade(&five);
+/// assert_eq!(*weak_five.upgrade().unwrap(), 5);
+///
+/// drop(five);
+/// assert!(weak_five.upgrade().is_none());
+/// ```
+pub struct Weak<T: ?Sized> {
+    // `usize::MAX` if created by `Weak::new`, which doesn't allocate anything. It's not a valid
+    // address of `ArcInner`, whose alignment is larger than 1.
+    ptr: NonNull<ArcInner<T>>,
+}
+
+unsafe impl<T: ?Sized + Sync + Send> Send for Weak<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for Weak<T> {}
+
+impl<T> Weak<T> {
+    /// Constructs a new `Weak<T>`, without allocating any memory. Calling `upgrade` on the return
+    /// value always gives `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Weak;
+    ///
+    /// let empty: Weak<i64> = Weak::new();
+    /// assert!(empty.upgrade().is_none());
+    /// ```
+    pub fn new() -> Weak<T> {
+        Weak {
+            ptr: NonNull::new(usize::MAX as *mut ArcInner<T>).unwrap(),
+        }
+    }
+}
+
+impl<T: ?Sized> Weak<T> {
+    /// Attempts to upgrade the `Weak` pointer to an `Arc`, delaying dropping of the inner value if
+    /// successful.
+    ///
+    /// Returns `None` if the inner value has since been dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let five = Arc::new(5);
+    ///
+    /// let weak_five = Arc::downgrade(&five);
+    ///
+    /// let strong_five = weak_five.upgrade();
+    /// assert!(strong_five.is_some());
+    ///
+    /// // Destroy all strong pointers.
+    /// drop(strong_five);
+    /// drop(five);
+    ///
+    /// assert!(weak_five.upgrade().is_none());
+    /// ```
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        let inner = self.inner()?;
+        let mut count = inner.strong.load(Ordering::Relaxed);
+        loop {
+            // The data is already dropped, or being moved out by `try_unwrap` or `make_mut`.
+            if count == 0 {
+                return None;
+            }
+            if count == MAX_REFCOUNT {
+                panic!();
+            }
+            // Acquire synchronizes with the release in `make_mut`, so that the access by the new
+            // `Arc` happens-after the exclusive borrow ends.
+            match inner.strong.compare_exchange_weak(
+                count,
+                count + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(Arc::from_inner(self.ptr)),
+                Err(old) => count = old,
+            }
+        }
+    }
+
+    /// Returns the counters and the layout, or `None` if the `Weak` is created by `Weak::new`.
+    ///
+    /// The data may be dropped or not initialized yet, so only the counters are referenced.
+    #[inline]
+    fn inner(&self) -> Option<WeakInner<'_>> {
+        if self.ptr.as_ptr() as *mut u8 as usize == usize::MAX {
+            None
+        } else {
+            // The allocation is alive while this `Weak` is alive.
+            let ptr = self.ptr.as_ptr();
+            unsafe {
+                Some(WeakInner {
+                    strong: &(*ptr).strong,
+                    weak: &(*ptr).weak,
+                    layout: &(*ptr).layout,
+                })
+            }
+        }
+    }
+}
+
+/// The counters of an allocation referenced by a `Weak`.
+struct WeakInner<'a> {
+    strong: &'a AtomicUsize,
+    weak: &'a AtomicUsize,
+    layout: &'a Layout,
+}
+
+impl<T: ?Sized> Clone for Weak<T> {
+    /// Makes a clone of the `Weak` pointer that points to the same allocation.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the number of `Weak`s is larger than `isize::Max`.
+    #[inline]
+    fn clone(&self) -> Weak<T> {
+        if let Some(inner) = self.inner() {
+            // The weak count is not locked, since there is a `Weak`.
+            let count = inner.weak.fetch_add(1, Ordering::Relaxed);
+            if count == MAX_REFCOUNT {
+                panic!();
+            }
+        }
+        Weak { ptr: self.ptr }
+    }
+}
+
+impl<T> Default for Weak<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> Drop for Weak<T> {
+    /// Drops the `Weak` pointer.
+    ///
+    /// This will decrement the weak count. If the weak count reaches zero, the allocation is
+    /// freed.
+    fn drop(&mut self) {
+        let inner = if let Some(inner) = self.inner() {
+            inner
+        } else {
+            return;
+        };
+        if inner.weak.fetch_sub(1, Ordering::Release) == 1 {
+            // Synchronizes with the other `Weak`s' drop and the last `Arc`'s drop.
+            fence(Ordering::Acquire);
+            // The data is already dropped, so only the allocation is freed.
+            let layout = *inner.layout;
+            unsafe { dealloc(self.ptr.as_ptr() as *mut u8, layout) };
+        }
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Weak<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(Weak)")
+    }
+}
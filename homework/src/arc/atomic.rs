@@ -0,0 +1,172 @@
+//! Atomically swappable `Arc`.
+
+use std::fmt;
+use std::mem::{self, ManuallyDrop};
+use std::ptr::NonNull;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::Ordering;
+#[cfg(not(feature = "check-loom"))]
+use std::sync::atomic::Ordering;
+
+use super::{Arc, ArcInner};
+use crate::hazard_pointer::{get_protected, retire_with, Atomic, Shared};
+
+/// An atomic cell holding an `Arc`, which can be loaded and replaced concurrently.
+///
+/// The cell holds a strong reference to its current `Arc`. `load` clones the current `Arc`, which
+/// has to increment the strong count of an allocation that may be concurrently replaced. If the
+/// replacing thread released the cell's reference right away, the strong count might reach zero
+/// and the allocation might be freed before `load` increments it. So `load` protects the
+/// allocation with a hazard pointer before incrementing the strong count, and the replaced
+/// reference is `retire`d, i.e., released after all the hazard pointers to it are dropped. Since
+/// the strong count is positive while the allocation is protected, `load` never increments a zero
+/// strong count.
+///
+/// # Examples
+///
+/// ```
+/// use cs492_concur_homework::{Arc, AtomicArc};
+///
+/// let config = AtomicArc::new(Arc::new(1));
+/// assert_eq!(*config.load(), 1);
+///
+/// let old = config.swap(Arc::new(2));
+/// assert_eq!(*old, 1);
+/// assert_eq!(*config.load(), 2);
+/// ```
+pub struct AtomicArc<T> {
+    ptr: Atomic<ArcInner<T>>,
+}
+
+impl<T: Send + Sync + 'static> AtomicArc<T> {
+    /// Constructs a new `AtomicArc<T>` holding `arc`.
+    pub fn new(arc: Arc<T>) -> Self {
+        Self {
+            ptr: Atomic::from(into_shared(arc)),
+        }
+    }
+
+    /// Loads the current `Arc`.
+    pub fn load(&self) -> Arc<T> {
+        let shield = get_protected(&self.ptr).unwrap();
+        // The cell's reference is not released while the allocation is protected.
+        unsafe { clone_shared(shield.shared()) }
+    }
+
+    /// Stores `new` into the cell, releasing the previous `Arc`.
+    pub fn store(&self, new: Arc<T>) {
+        let old = self.ptr.swap(into_shared(new), Ordering::AcqRel);
+        retire_arc(old);
+    }
+
+    /// Stores `new` into the cell, returning the previous `Arc`.
+    pub fn swap(&self, new: Arc<T>) -> Arc<T> {
+        let old = self.ptr.swap(into_shared(new), Ordering::AcqRel);
+        // The cell's reference is not released until it is retired.
+        let result = unsafe { clone_shared(old) };
+        retire_arc(old);
+        result
+    }
+
+    /// Stores `new` into the cell if the current `Arc` points to the same allocation as `current`
+    /// (in a vein similar to `Arc::ptr_eq`).
+    ///
+    /// The return value is a result indicating whether `new` was stored. On failure `new` is
+    /// returned.
+    ///
+    /// Since `current` keeps its allocation alive, another allocation can't reuse its address, so
+    /// the comparison is free from the ABA problem.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::{Arc, AtomicArc};
+    ///
+    /// let config = AtomicArc::new(Arc::new(1));
+    /// let current = config.load();
+    ///
+    /// assert!(config.compare_and_swap(&current, Arc::new(2)).is_ok());
+    /// assert_eq!(*config.compare_and_swap(&current, Arc::new(3)).unwrap_err(), 3);
+    /// assert_eq!(*config.load(), 2);
+    /// ```
+    pub fn compare_and_swap(&self, current: &Arc<T>, new: Arc<T>) -> Result<(), Arc<T>> {
+        let current = Shared::from_usize(current.ptr.as_ptr() as usize);
+        let new = into_shared(new);
+        match self
+            .ptr
+            .compare_and_set(current, new, Ordering::AcqRel, Ordering::Relaxed)
+        {
+            Ok(()) => {
+                retire_arc(current);
+                Ok(())
+            }
+            Err(_) => Err(unsafe { from_shared(new) }),
+        }
+    }
+
+    /// Consumes the cell, returning the current `Arc`.
+    pub fn into_inner(self) -> Arc<T> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        mem::forget(self);
+        unsafe { from_shared(ptr) }
+    }
+}
+
+/// Converts `arc` into a pointer, which holds the strong reference of `arc`.
+fn into_shared<T>(arc: Arc<T>) -> Shared<ArcInner<T>> {
+    let shared = Shared::from_usize(arc.ptr.as_ptr() as usize);
+    mem::forget(arc);
+    shared
+}
+
+/// Converts `ptr` back into the `Arc` that holds its strong reference.
+///
+/// # Safety
+///
+/// `ptr` should be created by `into_shared`, and this should be called once per `into_shared`.
+unsafe fn from_shared<T>(ptr: Shared<ArcInner<T>>) -> Arc<T> {
+    Arc::from_inner(NonNull::new_unchecked(ptr.into_usize() as *mut ArcInner<T>))
+}
+
+/// Clones the `Arc` of `ptr` without consuming its strong reference.
+///
+/// # Safety
+///
+/// `ptr` should be created by `into_shared` and its strong reference should not be released yet.
+unsafe fn clone_shared<T>(ptr: Shared<ArcInner<T>>) -> Arc<T> {
+    let arc = ManuallyDrop::new(from_shared(ptr));
+    Arc::clone(&arc)
+}
+
+/// Releases the strong reference of `ptr` after no threads are loading it.
+fn retire_arc<T: Send + Sync + 'static>(ptr: Shared<ArcInner<T>>) {
+    retire_with(ptr, |ptr: *mut ArcInner<T>| unsafe {
+        drop(Arc::from_inner(NonNull::new_unchecked(ptr)))
+    });
+}
+
+impl<T> Drop for AtomicArc<T> {
+    fn drop(&mut self) {
+        // The cell is owned, so no threads are loading it.
+        unsafe { drop(from_shared(self.ptr.load(Ordering::Relaxed))) };
+    }
+}
+
+impl<T: Default + Send + Sync + 'static> Default for AtomicArc<T> {
+    fn default() -> Self {
+        Self::new(Arc::new(T::default()))
+    }
+}
+
+impl<T: Send + Sync + 'static> From<Arc<T>> for AtomicArc<T> {
+    fn from(arc: Arc<T>) -> Self {
+        Self::new(arc)
+    }
+}
+
+impl<T: fmt::Debug + Send + Sync + 'static> fmt::Debug for AtomicArc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicArc").field(&self.load()).finish()
+    }
+}
@@ -0,0 +1,587 @@
+//! `Arc` with biased reference counting.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::ptr::{self, NonNull};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::thread_local;
+#[cfg(not(feature = "check-loom"))]
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+#[cfg(not(feature = "check-loom"))]
+use std::thread_local;
+
+use super::{Arc, MAX_REFCOUNT};
+use crate::hazard_pointer::thread_key;
+
+/// `Arc` with biased reference counting, which is efficient when most of the `clone`s and `drop`s
+/// happen on the thread that created it.
+///
+/// The counter is split into two ([Biased Reference Counting by Choi et
+/// al.](https://doi.org/10.1145/3243176.3243195)). The thread that creates a `BiasedArc` becomes
+/// the owner of the allocation, and updates the _biased_ counter only with plain loads and stores.
+/// The other threads update the _shared_ counter with RMWs. The number of `BiasedArc`s is the sum
+/// of the two counters, so the shared counter may be negative if other threads drop the
+/// `BiasedArc`s cloned by the owner.
+///
+/// The two counters are _merged_ into the shared counter when the owner can't update the biased
+/// counter any more, after which the allocation has no owner:
+///
+/// - Implicit merge: if the biased counter reaches zero, the owner marks the shared counter as
+///   merged. The allocation is freed if the shared counter is zero.
+///
+/// - Explicit merge: if the shared counter becomes negative, the thread marks the shared counter
+///   as queued and pushes the allocation to the owner's queue, because the allocation may be
+///   freed only after the biased counter is added. The owner merges the queued allocations on
+///   `new`, `get_mut`-like methods, and thread exit. After the owner exits, the pushing thread
+///   merges it by itself.
+///
+/// Once merged, the shared counter is the number of `BiasedArc`s and the allocation is freed when
+/// it reaches zero as in `Arc`.
+///
+/// Unlike `Arc`, `BiasedArc` doesn't support `Weak` pointers and unsized data.
+///
+/// # Examples
+///
+/// ```
+/// use cs492_concur_homework::BiasedArc;
+/// use std::thread;
+///
+/// let five = BiasedArc::new(5);
+/// let local = BiasedArc::clone(&five); // Updates the biased counter.
+///
+/// thread::spawn(move || {
+///     let remote = BiasedArc::clone(&local); // Updates the shared counter.
+///     assert_eq!(*remote, 5);
+/// })
+/// .join()
+/// .unwrap();
+///
+/// assert_eq!(BiasedArc::count(&five), 1);
+/// ```
+pub struct BiasedArc<T> {
+    ptr: NonNull<BiasedInner<T>>,
+    phantom: PhantomData<BiasedInner<T>>,
+}
+
+unsafe impl<T: Sync + Send> Send for BiasedArc<T> {}
+unsafe impl<T: Sync + Send> Sync for BiasedArc<T> {}
+
+/// The header of an allocation, which is accessed without the type of the data by the queue.
+struct Header {
+    /// The key of the owner thread, or `UNBIASED` if merged.
+    owner: AtomicUsize,
+    /// Updated only by the owner. After the owner exits, it's read by the thread that merges.
+    biased: AtomicUsize,
+    /// `count << SHIFT | QUEUED | MERGED`, where `count` is signed.
+    shared: AtomicUsize,
+    /// The owner's queue. `None` if created without an owner.
+    queue: Option<Arc<Queue>>,
+    /// The next allocation in the owner's queue.
+    next: AtomicPtr<Header>,
+    /// Merges the counters of the allocation. `merge::<T>` for `BiasedInner<T>`.
+    merge: unsafe fn(*mut Header),
+}
+
+// `repr(C)` places `header` at the start, so that `*mut Header` can be cast to `*mut BiasedInner<T>`.
+#[repr(C)]
+struct BiasedInner<T> {
+    header: Header,
+    data: T,
+}
+
+unsafe impl<T: Sync + Send> Send for BiasedInner<T> {}
+unsafe impl<T: Sync + Send> Sync for BiasedInner<T> {}
+
+const UNBIASED: usize = 0;
+
+const MERGED: usize = 1;
+const QUEUED: usize = 2;
+const SHIFT: usize = 2;
+const ONE: usize = 1 << SHIFT;
+
+/// Returns the signed count of a shared counter.
+#[inline]
+fn count(shared: usize) -> isize {
+    (shared as isize) >> SHIFT
+}
+
+/// The queue of allocations to be merged by the owner.
+///
+/// A lock-free stack of intrusive `Header`s. The head is `CLOSED` after the owner exits.
+#[derive(Debug)]
+struct Queue {
+    head: AtomicPtr<Header>,
+}
+
+impl Queue {
+    // Never a valid address of a `Header`.
+    const CLOSED: *mut Header = NonNull::dangling().as_ptr();
+
+    fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Pushes `header` to the queue. Returns `false` if the owner exited.
+    fn push(&self, header: *mut Header) -> bool {
+        // Acquire synchronizes with the release in `close`, so that the owner's last updates of
+        // the biased counter happen-before the merge by the current thread.
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            if head == Self::CLOSED {
+                return false;
+            }
+            unsafe { (*header).next.store(head, Ordering::Relaxed) };
+            // Release synchronizes with the acquire in `take` and `close`. The failure ordering
+            // can't be stronger than the success ordering, so acquire is also on success.
+            match self
+                .head
+                .compare_exchange(head, header, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return true,
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    /// Takes all the queued allocations.
+    fn take(&self) -> *mut Header {
+        if self.head.load(Ordering::Relaxed).is_null() {
+            return ptr::null_mut();
+        }
+        self.head.swap(ptr::null_mut(), Ordering::Acquire)
+    }
+
+    /// Takes all the queued allocations and prevents further pushes.
+    fn close(&self) -> *mut Header {
+        self.head.swap(Self::CLOSED, Ordering::AcqRel)
+    }
+}
+
+/// Merges the allocations in the list starting from `head`.
+unsafe fn merge_all(mut head: *mut Header) {
+    while !head.is_null() {
+        // `head` may be freed by the merge.
+        let next = (*head).next.load(Ordering::Relaxed);
+        ((*head).merge)(head);
+        head = next;
+    }
+}
+
+/// The current thread as an owner.
+struct Local {
+    key: usize,
+    queue: Arc<Queue>,
+}
+
+impl Local {
+    fn new() -> Self {
+        Self {
+            key: thread_key(),
+            queue: Arc::new(Queue::new()),
+        }
+    }
+
+    /// Merges the allocations pushed by the other threads.
+    fn merge_queued(&self) {
+        unsafe { merge_all(self.queue.take()) };
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        // From now on, the current thread updates the shared counters as a non-owner, since
+        // `LOCAL` is not accessible. So the biased counters are never updated again, and the
+        // threads that find the queue closed merge the counters by themselves.
+        unsafe { merge_all(self.queue.close()) };
+    }
+}
+
+thread_local! {
+    static LOCAL: Local = Local::new();
+}
+
+/// Merges the counters of a queued allocation, and frees it if there are no `BiasedArc`s.
+///
+/// # Safety
+///
+/// Should be called by the owner, or by the thread that found the owner exited. `ptr` should point
+/// to a `BiasedInner<T>` that is queued.
+unsafe fn merge<T>(ptr: *mut Header) {
+    let header = &*ptr;
+    let biased = header.biased.load(Ordering::Relaxed);
+    header.biased.store(0, Ordering::Relaxed);
+    header.owner.store(UNBIASED, Ordering::Relaxed);
+
+    // Release synchronizes with the acquire in the last `drop`, so that the owner's accesses
+    // happen-before the deallocation.
+    let mut shared = header.shared.load(Ordering::Relaxed);
+    let merged = loop {
+        let new = (shared.wrapping_add(biased << SHIFT) | MERGED) & !QUEUED;
+        match header
+            .shared
+            .compare_exchange(shared, new, Ordering::AcqRel, Ordering::Relaxed)
+        {
+            Ok(_) => break new,
+            Err(s) => shared = s,
+        }
+    };
+    if count(merged) == 0 {
+        drop(Box::from_raw(ptr as *mut BiasedInner<T>));
+    }
+}
+
+impl<T: 'static> BiasedArc<T> {
+    /// Constructs a new `BiasedArc<T>` owned by the current thread.
+    ///
+    /// `T: 'static` is required because the data may be dropped by the owner after the last
+    /// `BiasedArc` is dropped by another thread.
+    pub fn new(data: T) -> BiasedArc<T> {
+        // The thread is exiting if `LOCAL` is not accessible, so the allocation has no owner.
+        let (owner, queue) = LOCAL
+            .try_with(|local| {
+                local.merge_queued();
+                (local.key, Some(local.queue.clone()))
+            })
+            .unwrap_or((UNBIASED, None));
+        let (biased, shared) = if owner == UNBIASED {
+            (0, ONE | MERGED)
+        } else {
+            (1, 0)
+        };
+
+        let x = Box::new(BiasedInner {
+            header: Header {
+                owner: AtomicUsize::new(owner),
+                biased: AtomicUsize::new(biased),
+                shared: AtomicUsize::new(shared),
+                queue,
+                next: AtomicPtr::new(ptr::null_mut()),
+                merge: merge::<T>,
+            },
+            data,
+        });
+        Self::from_inner(Box::leak(x).into())
+    }
+}
+
+impl<T> BiasedArc<T> {
+    fn from_inner(ptr: NonNull<BiasedInner<T>>) -> Self {
+        Self {
+            ptr,
+            phantom: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn inner(&self) -> &BiasedInner<T> {
+        // The allocation is valid while this `BiasedArc` is alive.
+        unsafe { self.ptr.as_ref() }
+    }
+
+    #[inline]
+    fn header(&self) -> &Header {
+        &self.inner().header
+    }
+
+    /// Returns `true` if the current thread is the owner.
+    #[inline]
+    fn is_owner(&self) -> bool {
+        let owner = self.header().owner.load(Ordering::Relaxed);
+        owner != UNBIASED && LOCAL.try_with(|local| local.key == owner).unwrap_or(false)
+    }
+
+    /// Returns a mutable reference into the given `BiasedArc` if there are no other `BiasedArc`.
+    /// Otherwise, return `None`.
+    ///
+    /// On a thread other than the owner, this also returns `None` until the counters are merged,
+    /// since the biased counter may be updated by the owner concurrently.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::BiasedArc;
+    ///
+    /// let mut x = BiasedArc::new(3);
+    /// *BiasedArc::get_mut(&mut x).unwrap() = 4;
+    /// assert_eq!(*x, 4);
+    ///
+    /// let y = BiasedArc::clone(&x);
+    /// assert!(BiasedArc::get_mut(&mut x).is_none());
+    ///
+    /// drop(y);
+    /// assert!(BiasedArc::get_mut(&mut x).is_some());
+    /// ```
+    #[inline]
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        if this.is_unique() {
+            unsafe { Some(Self::get_mut_unchecked(this)) }
+        } else {
+            None
+        }
+    }
+
+    // Used in `get_mut`, `make_mut` and `try_unwrap` to check if the given `BiasedArc` is the
+    // unique reference to the underlying data.
+    fn is_unique(&mut self) -> bool {
+        let header = self.header();
+        // Acquire synchronizes with the release of the other threads' `drop`.
+        let mut shared = header.shared.load(Ordering::Acquire);
+        if self.is_owner() {
+            if shared & QUEUED == 0 {
+                let biased = header.biased.load(Ordering::Relaxed) as isize;
+                return biased + count(shared) == 1;
+            }
+            // The allocation may be freed after the uniqueness check, so it shouldn't be in the
+            // queue. Merge it now.
+            LOCAL.with(Local::merge_queued);
+            shared = header.shared.load(Ordering::Acquire);
+        }
+        shared & (MERGED | QUEUED) == MERGED && count(shared) == 1
+    }
+
+    /// Returns a mutable reference into the given `BiasedArc` without any check.
+    ///
+    /// # Safety
+    ///
+    /// Any other `BiasedArc` to the same allocation must not be dereferenced for the duration of
+    /// the returned borrow.  Specifically, call to this function must happen-after destruction of
+    /// all the other `BiasedArc` to the same allocation.
+    pub unsafe fn get_mut_unchecked(this: &mut Self) -> &mut T {
+        // We are careful to *not* create a reference covering the counter fields, as this would
+        // alias with concurrent access to the reference counts.
+        &mut (*this.ptr.as_ptr()).data
+    }
+
+    /// Gets the number of `BiasedArc`s to this allocation.
+    ///
+    /// # Safety
+    ///
+    /// This method by itself is safe, but using it correctly requires extra care. Another thread
+    /// can change the reference count at any time, including potentially between calling this
+    /// method and acting on the result. Furthermore, on a thread other than the owner, the biased
+    /// counter may be read out of date.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::BiasedArc;
+    ///
+    /// let five = BiasedArc::new(5);
+    /// let _also_five = BiasedArc::clone(&five);
+    ///
+    /// // This assertion is deterministic because we haven't shared
+    /// // the `BiasedArc` between threads.
+    /// assert_eq!(2, BiasedArc::count(&five));
+    /// ```
+    #[inline]
+    pub fn count(this: &Self) -> usize {
+        let header = this.header();
+        let shared = header.shared.load(Ordering::Acquire);
+        let biased = header.biased.load(Ordering::Relaxed) as isize;
+        (biased + count(shared)) as usize
+    }
+
+    /// Returns `true` if the two `BiasedArc`s point to the same allocation
+    /// (in a vein similar to `ptr::eq`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::BiasedArc;
+    ///
+    /// let five = BiasedArc::new(5);
+    /// let same_five = BiasedArc::clone(&five);
+    /// let other_five = BiasedArc::new(5);
+    ///
+    /// assert!(BiasedArc::ptr_eq(&five, &same_five));
+    /// assert!(!BiasedArc::ptr_eq(&five, &other_five));
+    /// ```
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.ptr.as_ptr() == other.ptr.as_ptr()
+    }
+
+    /// Returns the inner value, if the given `BiasedArc` is unique.
+    ///
+    /// Otherwise, an `Err` is returned with the same `BiasedArc` that was passed in. As in
+    /// `get_mut`, this fails on a thread other than the owner until the counters are merged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::BiasedArc;
+    ///
+    /// let x = BiasedArc::new(3);
+    /// assert_eq!(BiasedArc::try_unwrap(x).unwrap(), 3);
+    ///
+    /// let x = BiasedArc::new(4);
+    /// let _y = BiasedArc::clone(&x);
+    /// assert_eq!(*BiasedArc::try_unwrap(x).unwrap_err(), 4);
+    /// ```
+    pub fn try_unwrap(mut this: Self) -> Result<T, Self> {
+        if !this.is_unique() {
+            return Err(this);
+        }
+        // The allocation is not queued and there are no other `BiasedArc`s, so it's freed right
+        // away.
+        let inner = unsafe { Box::from_raw(this.ptr.as_ptr()) };
+        mem::forget(this);
+        let BiasedInner { data, .. } = *inner;
+        Ok(data)
+    }
+}
+
+impl<T: Clone + 'static> BiasedArc<T> {
+    /// Makes a mutable reference into the given `BiasedArc`.
+    ///
+    /// If there are other `BiasedArc` to the same allocation, then `make_mut` will create a new
+    /// allocation owned by the current thread and invoke `clone` on the inner value to ensure
+    /// unique ownership. This is also referred to as clone-on-write.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::BiasedArc;
+    ///
+    /// let mut data = BiasedArc::new(5);
+    ///
+    /// *BiasedArc::make_mut(&mut data) += 1;               // Won't clone anything
+    /// let mut other_data = BiasedArc::clone(&data); // Won't clone inner data
+    /// *BiasedArc::make_mut(&mut data) += 1;               // Clones inner data
+    /// *BiasedArc::make_mut(&mut data) += 1;               // Won't clone anything
+    /// *BiasedArc::make_mut(&mut other_data) *= 2;         // Won't clone anything
+    ///
+    /// // Now `data` and `other_data` point to different allocations.
+    /// assert_eq!(*data, 8);
+    /// assert_eq!(*other_data, 12);
+    /// ```
+    pub fn make_mut(this: &mut Self) -> &mut T {
+        if !this.is_unique() {
+            *this = BiasedArc::new(this.inner().data.clone());
+        }
+        unsafe { Self::get_mut_unchecked(this) }
+    }
+}
+
+impl<T> Clone for BiasedArc<T> {
+    /// Makes a clone of the `BiasedArc` pointer.
+    ///
+    /// This increments the biased counter on the owner, and the shared counter otherwise.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the number of `BiasedArc`s is larger than `isize::Max`.
+    #[inline]
+    fn clone(&self) -> BiasedArc<T> {
+        let header = self.header();
+        if self.is_owner() {
+            let biased = header.biased.load(Ordering::Relaxed);
+            if biased == MAX_REFCOUNT {
+                panic!();
+            }
+            header.biased.store(biased + 1, Ordering::Relaxed);
+        } else {
+            let shared = header.shared.fetch_add(ONE, Ordering::Relaxed);
+            if count(shared) >= (MAX_REFCOUNT >> SHIFT) as isize {
+                panic!();
+            }
+        }
+        Self::from_inner(self.ptr)
+    }
+}
+
+impl<T> Deref for BiasedArc<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.inner().data
+    }
+}
+
+impl<T> Drop for BiasedArc<T> {
+    /// Drops the `BiasedArc`.
+    ///
+    /// This decrements the biased counter on the owner, and the shared counter otherwise. The
+    /// counters are merged if needed, and the inner value is dropped if there are no `BiasedArc`s.
+    fn drop(&mut self) {
+        let header = self.header();
+        if self.is_owner() {
+            let biased = header.biased.load(Ordering::Relaxed) - 1;
+            header.biased.store(biased, Ordering::Relaxed);
+            if biased != 0 {
+                return;
+            }
+
+            // Implicit merge. Release synchronizes with the acquire in the last `drop`.
+            header.owner.store(UNBIASED, Ordering::Relaxed);
+            let shared = header.shared.fetch_or(MERGED, Ordering::AcqRel);
+            // If queued, the explicit merge frees it.
+            if shared & QUEUED == 0 && count(shared) == 0 {
+                unsafe { drop(Box::from_raw(self.ptr.as_ptr())) };
+            }
+            return;
+        }
+
+        let mut shared = header.shared.load(Ordering::Relaxed);
+        let (new, queue) = loop {
+            let mut new = shared.wrapping_sub(ONE);
+            // The count becomes negative only if not merged.
+            let queue = count(new) < 0 && shared & QUEUED == 0;
+            if queue {
+                new |= QUEUED;
+            }
+            // Release synchronizes with the acquire in the last `drop`, the owner's merge, and
+            // `is_unique`. Acquire synchronizes with the release of the others.
+            match header
+                .shared
+                .compare_exchange(shared, new, Ordering::AcqRel, Ordering::Relaxed)
+            {
+                Ok(_) => break (new, queue),
+                Err(s) => shared = s,
+            }
+        };
+
+        if queue {
+            let ptr = self.ptr.as_ptr() as *mut Header;
+            // A queued allocation always has an owner.
+            if !header.queue.as_ref().unwrap().push(ptr) {
+                unsafe { merge::<T>(ptr) };
+            }
+        } else if new & (MERGED | QUEUED) == MERGED && count(new) == 0 {
+            unsafe { drop(Box::from_raw(self.ptr.as_ptr())) };
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for BiasedArc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for BiasedArc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> fmt::Pointer for BiasedArc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Pointer::fmt(&(&**self as *const T), f)
+    }
+}
+
+impl<T: 'static> From<T> for BiasedArc<T> {
+    /// Moves `t` into a new `BiasedArc`.
+    fn from(t: T) -> Self {
+        BiasedArc::new(t)
+    }
+}
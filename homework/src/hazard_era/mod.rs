@@ -0,0 +1,135 @@
+//! Hazard eras.
+//!
+//! The API is close to that of `hazard_pointer`, so that a data structure
+//! can switch between them. The differences are that a shield is created only from an [`Atomic`],
+//! and that it need not be validated.
+//!
+//! # Example
+//!
+//! ```
+//! use std::sync::atomic::Ordering;
+//! use cs492_concur_homework::hazard_era::{get_protected, retire, collect, Atomic, Shared};
+//!
+//! let atomic = Atomic::new(1);
+//! let shield = get_protected(&atomic).unwrap();
+//! assert_eq!(unsafe { *shield.deref() }, 1);
+//!
+//! // unlink the block and retire
+//! atomic.store(Shared::null(), Ordering::Relaxed);
+//! retire(shield.shared());
+//!
+//! // manually trigger reclamation (not necessary)
+//! collect();
+//! ```
+//!
+//! # Algorithm
+//!
+//! Hazard eras (Ramalhete and Correia, SPAA 2017) combine hazard pointers with epochs. A global
+//! era clock advances as objects are reclaimed. Each object is stamped with the era of its
+//! allocation (birth era) and that of its retirement (retire era), so that it is accessible only
+//! in the eras between them. Instead of the pointer itself, a thread reserves the current era in
+//! its hazard array before reading a pointer. A retired object is freed if no reserved era is in
+//! its lifetime.
+//!
+//! While the clock stays at the same era, a shield protects the next pointer without issuing a
+//! fence or retrying, which makes traversal as cheap as with epochs. Unlike epochs, a stalled thread
+//! holds only the objects alive in its reserved eras, so the memory usage stays bounded.
+//!
+//! The retire era is read with a read-modify-write, so that a thread that reserves a later era
+//! synchronizes with the retirement and does not see the unlinked object. Reservations and scans
+//! are ordered by SC fences, in the same way as hazard pointers.
+
+use core::cell::RefCell;
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "check-loom")]
+use loom::thread_local;
+#[cfg(not(feature = "check-loom"))]
+use std::thread_local;
+
+mod atomic;
+mod retire;
+mod shield;
+
+pub use atomic::{Atomic, Owned, Shared};
+pub use shield::Shield;
+
+use crate::hazard_pointer::hazard::{thread_key, Hazards, Registration};
+use retire::{Orphans, Retirees};
+
+/// The reserved era of a free slot. The clock starts from the next era.
+const NONE: usize = 0;
+
+#[cfg(not(feature = "check-loom"))]
+/// Global era clock.
+static ERA: AtomicUsize = AtomicUsize::new(NONE + 1);
+
+#[cfg(not(feature = "check-loom"))]
+/// Global set of all reserved eras.
+static RESERVATIONS: Hazards = Hazards::new();
+
+#[cfg(not(feature = "check-loom"))]
+/// Global list of the retired objects left by exited threads.
+static ORPHANS: Orphans = Orphans::new();
+
+#[cfg(feature = "check-loom")]
+loom::lazy_static! {
+    /// Global era clock.
+    static ref ERA: AtomicUsize = AtomicUsize::new(NONE + 1);
+
+    /// Global set of all reserved eras.
+    static ref RESERVATIONS: Hazards = Hazards::new();
+
+    /// Global list of the retired objects left by exited threads.
+    static ref ORPHANS: Orphans = Orphans::new();
+}
+
+thread_local! {
+    /// Registration of the current thread. The thread's reservations are reused by a new thread
+    /// after the thread exits.
+    static REGISTRATION: Registration<'static> = Registration::new(&RESERVATIONS);
+
+    /// Thread-local list of retired objects.
+    static RETIRED: RefCell<Retirees<'static>> = RefCell::new(Retirees::new(&RESERVATIONS, &ORPHANS));
+}
+
+/// Returns the current era.
+fn current_era() -> usize {
+    ERA.load(Ordering::Acquire)
+}
+
+/// Returns the current era as the retire era of an object unlinked by the current thread.
+fn retire_era() -> usize {
+    ERA.fetch_add(0, Ordering::AcqRel)
+}
+
+/// Advances the era clock.
+fn advance_era() {
+    let _ = ERA.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Returns a shield that protects the pointer loaded from `atomic`.
+///
+/// The current thread's reservation array grows as needed, so this always returns `Some`.
+pub fn get_protected<T>(atomic: &Atomic<T>) -> Option<Shield<'static, T>> {
+    // The registration may be already destroyed if the current thread is exiting.
+    let eras = REGISTRATION
+        .try_with(|r| r.local())
+        .unwrap_or_else(|_| RESERVATIONS.get(thread_key()));
+    Some(unsafe { Shield::new(atomic, eras) })
+}
+
+/// Retires a pointer.
+pub fn retire<T>(pointer: Shared<T>) {
+    RETIRED.with(|r| r.borrow_mut().retire(pointer));
+}
+
+/// Frees the pointers that are `retire`d by the current thread and not accessible in the eras
+/// reserved by any threads.
+pub fn collect() {
+    RETIRED.with(|r| r.borrow_mut().collect());
+}
@@ -0,0 +1,106 @@
+use core::mem;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{fence, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{fence, Ordering};
+
+use super::atomic::Shared;
+use crate::hazard_pointer::hazard::Hazards;
+use crate::hazard_pointer::retire::{self as hp, SharedRetirees};
+
+/// A retired object, which may be accessed by the threads that reserved an era in
+/// `birth..=retire`.
+#[derive(Debug)]
+pub struct Retired {
+    /// The machine representation of the pointer without tag.
+    pointer: usize,
+    birth: usize,
+    retire: usize,
+    /// The function pointer to `free::<T>` where `T` is the type of the object.
+    free: unsafe fn(usize),
+}
+
+/// Global list of the retired objects left by exited threads.
+pub type Orphans = SharedRetirees<Retired>;
+
+/// Thread-local list of retired objects.
+pub struct Retirees<'s> {
+    reservations: &'s Hazards,
+    orphans: &'s Orphans,
+    inner: Vec<Retired>,
+    /// The length of retired object list that triggers `collect`, adapted to the number of
+    /// reservation slots on each `collect` in the same way as hazard pointers.
+    threshold: usize,
+}
+
+impl<'s> Retirees<'s> {
+    pub fn new(reservations: &'s Hazards, orphans: &'s Orphans) -> Self {
+        Self {
+            reservations,
+            orphans,
+            inner: Vec::new(),
+            threshold: hp::Retirees::MIN_THRESHOLD,
+        }
+    }
+
+    /// Retire a pointer.
+    pub fn retire<T>(&mut self, pointer: Shared<T>) {
+        unsafe fn free<T>(data: usize) {
+            drop(Shared::<T>::from_usize(data).into_owned())
+        }
+        self.inner.push(Retired {
+            pointer: pointer.with_tag(0).into_usize(),
+            birth: unsafe { pointer.birth_era() },
+            retire: super::retire_era(),
+            free: free::<T>,
+        });
+        if self.inner.len() > self.threshold {
+            self.collect();
+        }
+    }
+
+    /// Free the objects that are `retire`d by the current thread or orphaned by exited threads, and
+    /// not accessible in the eras reserved by any threads.
+    pub fn collect(&mut self) {
+        // The objects retired from now on can be accessed in the new era.
+        super::advance_era();
+        self.orphans.adopt(&mut self.inner);
+        fence(Ordering::SeqCst);
+
+        let mut eras = self
+            .reservations
+            .all_hazards()
+            .into_iter()
+            .filter(|&era| era != super::NONE)
+            .collect::<Vec<_>>();
+        eras.sort_unstable();
+
+        let mut index = 0;
+        while index != self.inner.len() {
+            let Retired { birth, retire, .. } = self.inner[index];
+            // The first reserved era not earlier than `birth`.
+            let first = match eras.binary_search(&birth) {
+                Ok(i) | Err(i) => eras.get(i),
+            };
+            if matches!(first, Some(&era) if era <= retire) {
+                index += 1;
+            } else {
+                let retired = self.inner.swap_remove(index);
+                unsafe { (retired.free)(retired.pointer) };
+            }
+        }
+        self.threshold = hp::Retirees::threshold(self.reservations);
+    }
+}
+
+// TODO(@tomtomjhj): this triggers loom internal bug
+#[cfg(not(feature = "check-loom"))]
+impl Drop for Retirees<'_> {
+    fn drop(&mut self) {
+        // The objects that may be still accessed are handed over to the other threads.
+        self.collect();
+        if !self.inner.is_empty() {
+            self.orphans.push(mem::take(&mut self.inner));
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use core::fmt;
+use core::marker::PhantomData;
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{fence, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{fence, Ordering};
+
+use super::atomic::{Atomic, Shared};
+use crate::hazard_pointer::hazard::LocalHazards;
+
+/// Represents the ownership of an era reservation slot.
+///
+/// The objects alive in the reserved era are not reclaimed until the shield is dropped or reserves
+/// another era.
+pub struct Shield<'s, T> {
+    data: usize, // preserves the tag of original `Shared`
+    era: usize,
+    eras: &'s LocalHazards,
+    index: usize,
+    _marker: PhantomData<&'s T>,
+}
+
+impl<'s, T> Shield<'s, T> {
+    /// Creates a new shield that protects the pointer loaded from `atomic`.
+    ///
+    /// # Safety
+    ///
+    /// This function must be called only by the thread that owns this reservation array.
+    pub(super) unsafe fn new(atomic: &Atomic<T>, eras: &'s LocalHazards) -> Self {
+        let mut shield = Self {
+            data: 0,
+            era: super::NONE,
+            eras,
+            index: eras.alloc(super::NONE),
+            _marker: PhantomData,
+        };
+        let _ = shield.protect(atomic);
+        shield
+    }
+
+    /// Protects the pointer loaded from `atomic` instead, and returns it.
+    ///
+    /// If the era clock hasn't advanced since the last protection, the reserved era already covers
+    /// the pointer, and no fence is issued.
+    pub fn protect(&mut self, atomic: &Atomic<T>) -> Shared<T> {
+        loop {
+            let shared = atomic.load(Ordering::Acquire);
+            let era = super::current_era();
+            if era == self.era {
+                self.data = shared.into_usize();
+                return shared;
+            }
+            unsafe { self.eras.set(self.index, era) };
+            fence(Ordering::SeqCst);
+            self.era = era;
+        }
+    }
+
+    /// Returns `true` if the pointer is null.
+    pub fn is_null(&self) -> bool {
+        self.shared().is_null()
+    }
+
+    /// Returns the `Shared` pointer protected by this shield. The original tag is preserved.
+    pub fn shared(&self) -> Shared<T> {
+        Shared::from_usize(self.data)
+    }
+
+    /// Dereferences the shielded pointer.
+    ///
+    /// # Safety
+    ///
+    /// The pointer should point to a valid object of type `T`. Invocations of this method should be
+    /// properly synchronized with the other accesses to the object in order to avoid data race.
+    pub unsafe fn deref(&self) -> &T {
+        &*(self.shared().deref() as *const T)
+    }
+
+    /// Dereferences the shielded pointer if the pointer is not null.
+    ///
+    /// # Safety
+    ///
+    /// The pointer should point to a valid object of type `T`. Invocations of this method should be
+    /// properly synchronized with the other accesses to the object in order to avoid data race.
+    pub unsafe fn as_ref(&self) -> Option<&T> {
+        if self.is_null() {
+            None
+        } else {
+            Some(self.deref())
+        }
+    }
+}
+
+impl<'s, T> Drop for Shield<'s, T> {
+    fn drop(&mut self) {
+        unsafe { self.eras.dealloc(self.index) };
+    }
+}
+
+impl<'s, T> fmt::Debug for Shield<'s, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shield")
+            .field("data", &self.data)
+            .field("era", &self.era)
+            .field("eras", &(self.eras as *const _))
+            .field("index", &self.index)
+            .finish()
+    }
+}
@@ -42,6 +42,30 @@ pub struct Shared<T> {
     _marker: PhantomData<*const T>,
 }
 
+/// A pointer that can be stored into an [`Atomic`]: [`Owned`] or [`Shared`].
+pub trait Pointer<T> {
+    /// Returns the machine representation of the pointer.
+    fn into_usize(self) -> usize;
+
+    /// Returns a new pointer pointing to the tagged pointer `data`.
+    ///
+    /// # Safety
+    ///
+    /// `data` should be the machine representation of a pointer of the same kind, e.g., the one
+    /// returned by `into_usize`.
+    unsafe fn from_usize(data: usize) -> Self;
+}
+
+/// The error returned on failed compare-and-exchange operations.
+#[derive(Debug)]
+pub struct CompareExchangeError<T, P: Pointer<T>> {
+    /// The value in the atomic pointer at the time of the failed operation.
+    pub current: Shared<T>,
+
+    /// The new value, which the operation failed to store.
+    pub new: P,
+}
+
 impl<T> Clone for Shared<T> {
     fn clone(&self) -> Self {
         Self {
@@ -101,6 +125,18 @@ impl<T> Owned<T> {
     }
 }
 
+impl<T> Pointer<T> for Owned<T> {
+    fn into_usize(self) -> usize {
+        let data = self.data;
+        mem::forget(self);
+        data
+    }
+
+    unsafe fn from_usize(data: usize) -> Self {
+        Self::from_usize(data)
+    }
+}
+
 impl<T> Deref for Owned<T> {
     type Target = T;
 
@@ -150,9 +186,15 @@ impl<T> Atomic<T> {
         Shared::from_usize(self.data.load(ord))
     }
 
-    /// Stores a `Shared` into the atomic pointer.
-    pub fn store(&self, data: Shared<T>, ord: Ordering) {
-        self.data.store(data.data, ord);
+    /// Stores an `Owned` or `Shared` pointer into the atomic pointer.
+    pub fn store<P: Pointer<T>>(&self, new: P, ord: Ordering) {
+        self.data.store(new.into_usize(), ord);
+    }
+
+    /// Stores an `Owned` or `Shared` pointer into the atomic pointer, returning the previous
+    /// pointer.
+    pub fn swap<P: Pointer<T>>(&self, new: P, ord: Ordering) -> Shared<T> {
+        Shared::from_usize(self.data.swap(new.into_usize(), ord))
     }
 
     /// Stores the `Shared` pointer `new` into the atomic pointer if the current value is the same
@@ -174,6 +216,61 @@ impl<T> Atomic<T> {
             .map_err(Shared::from_usize)
     }
 
+    /// Stores the pointer `new` into the atomic pointer if the current value is the same as
+    /// `current`. The tag is also taken into account, so two pointers to the same object, but with
+    /// different tags, will not be considered equal.
+    ///
+    /// The return value is a result indicating whether the new pointer was written. On success the
+    /// pointer that was written is returned. On failure the actual current value and `new` are
+    /// returned.
+    pub fn compare_exchange<P: Pointer<T>>(
+        &self,
+        current: Shared<T>,
+        new: P,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Shared<T>, CompareExchangeError<T, P>> {
+        let new = new.into_usize();
+        self.data
+            .compare_exchange(current.data, new, success, failure)
+            .map(|_| Shared::from_usize(new))
+            .map_err(|current| CompareExchangeError {
+                current: Shared::from_usize(current),
+                new: unsafe { P::from_usize(new) },
+            })
+    }
+
+    /// Stores the pointer `new` into the atomic pointer if the current value is the same as
+    /// `current`. The tag is also taken into account.
+    ///
+    /// Unlike `compare_exchange`, this function may fail spuriously even when the comparison
+    /// succeeds, which can result in more efficient code on some platforms. The return value is
+    /// the same as that of `compare_exchange`.
+    pub fn compare_exchange_weak<P: Pointer<T>>(
+        &self,
+        current: Shared<T>,
+        new: P,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Shared<T>, CompareExchangeError<T, P>> {
+        let new = new.into_usize();
+        self.data
+            .compare_exchange_weak(current.data, new, success, failure)
+            .map(|_| Shared::from_usize(new))
+            .map_err(|current| CompareExchangeError {
+                current: Shared::from_usize(current),
+                new: unsafe { P::from_usize(new) },
+            })
+    }
+
+    /// Performs a bitwise "and" operation on the current tag and the argument `tag`, and sets the
+    /// new tag to the result. Returns the previous pointer.
+    pub fn fetch_and(&self, tag: usize, ord: Ordering) -> Shared<T> {
+        let tag = tag | !align::low_bits::<T>();
+        let old = self.data.fetch_and(tag, ord);
+        Shared::from_usize(old)
+    }
+
     /// Performs a bitwise "or" operation on the current tag and the argument `tag`, and sets the
     /// new tag to the result. Returns the previous pointer.
     pub fn fetch_or(&self, tag: usize, ord: Ordering) -> Shared<T> {
@@ -181,6 +278,62 @@ impl<T> Atomic<T> {
         let old = self.data.fetch_or(tag, ord);
         Shared::from_usize(old)
     }
+
+    /// Performs a bitwise "xor" operation on the current tag and the argument `tag`, and sets the
+    /// new tag to the result. Returns the previous pointer.
+    pub fn fetch_xor(&self, tag: usize, ord: Ordering) -> Shared<T> {
+        let tag = tag & align::low_bits::<T>();
+        let old = self.data.fetch_xor(tag, ord);
+        Shared::from_usize(old)
+    }
+
+    /// Takes ownership of the pointee.
+    ///
+    /// # Safety
+    ///
+    /// The pointer should not be null, and the pointee should not be accessed by the other threads
+    /// any more, e.g., in the destructor of a data structure.
+    pub unsafe fn into_owned(self) -> Owned<T> {
+        self.load(Ordering::Relaxed).into_owned()
+    }
+
+    /// Takes ownership of the pointee if it is not null.
+    ///
+    /// # Safety
+    ///
+    /// The pointee should not be accessed by the other threads any more.
+    pub unsafe fn try_into_owned(self) -> Option<Owned<T>> {
+        let shared = self.load(Ordering::Relaxed);
+        if shared.is_null() {
+            None
+        } else {
+            Some(shared.into_owned())
+        }
+    }
+}
+
+impl<T> Default for Atomic<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> From<Owned<T>> for Atomic<T> {
+    fn from(owned: Owned<T>) -> Self {
+        Self {
+            data: AtomicUsize::new(owned.into_usize()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> From<Shared<T>> for Atomic<T> {
+    fn from(shared: Shared<T>) -> Self {
+        Self {
+            data: AtomicUsize::new(shared.into_usize()),
+            _marker: PhantomData,
+        }
+    }
 }
 
 impl<T> Shared<T> {
@@ -253,3 +406,13 @@ impl<T> Shared<T> {
         &*(data as *const T)
     }
 }
+
+impl<T> Pointer<T> for Shared<T> {
+    fn into_usize(self) -> usize {
+        self.data
+    }
+
+    unsafe fn from_usize(data: usize) -> Self {
+        Self::from_usize(data)
+    }
+}
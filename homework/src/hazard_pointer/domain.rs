@@ -0,0 +1,77 @@
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicUsize;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicUsize;
+
+use super::hazard::Hazards;
+use super::retire::{Retirees, SharedRetirees};
+use super::stats::{Counters, Stats};
+
+/// A reclamation domain: a set of hazard pointers and the pointers retired against them.
+///
+/// A pointer retired to a domain is freed once it is not protected by the shields of the domain, so
+/// a data structure with its own domain scans only its own hazard pointers. A pointer must be
+/// protected and retired in the same domain.
+///
+/// The free functions `protect`, `retire`, etc. use the default global domain, buffering the
+/// retired pointers per thread. The pointers retired to the other domains are kept in a list
+/// shared by the threads, and the remaining ones are freed when the domain is dropped.
+///
+/// A thread's hazard array in the default domain is released when the thread exits and reused by a
+/// new thread. In the other domains, it is released when the thread's last shield is dropped.
+#[derive(Debug)]
+pub struct Domain {
+    pub(super) hazards: Hazards,
+    pub(super) retired: SharedRetirees,
+    /// The number of retired pointers that triggers `collect`, adapted to the number of hazard
+    /// pointer slots on each `collect`.
+    pub(super) threshold: AtomicUsize,
+    /// The counters of reclamation events, reported by `stats`.
+    pub(super) counters: Counters,
+}
+
+impl Domain {
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates a new domain.
+    pub const fn new() -> Self {
+        Self {
+            hazards: Hazards::new(),
+            retired: SharedRetirees::new(),
+            threshold: AtomicUsize::new(Retirees::MIN_THRESHOLD),
+            counters: Counters::new(),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates a new domain.
+    pub fn new() -> Self {
+        Self {
+            hazards: Hazards::new(),
+            retired: SharedRetirees::new(),
+            threshold: AtomicUsize::new(Retirees::MIN_THRESHOLD),
+            counters: Counters::new(),
+        }
+    }
+
+    /// Returns a snapshot of the reclamation statistics of the domain.
+    pub fn stats(&self) -> Stats {
+        Stats::new(self)
+    }
+}
+
+impl Drop for Domain {
+    fn drop(&mut self) {
+        // No thread protects the pointers any more.
+        let mut retired = Vec::new();
+        self.retired.adopt(&mut retired);
+        for r in retired {
+            unsafe { r.reclaim() };
+        }
+    }
+}
+
+impl Default for Domain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
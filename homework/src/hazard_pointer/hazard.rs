@@ -1,29 +1,39 @@
 use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Index, IndexMut};
 use core::ptr;
-use std::collections::hash_map::DefaultHasher;
 use std::collections::HashSet;
 use std::fmt;
-use std::hash::{Hash, Hasher};
-use std::thread::ThreadId;
 
 #[cfg(not(feature = "check-loom"))]
 use core::sync::atomic::{AtomicPtr, AtomicU8, AtomicUsize, Ordering};
 #[cfg(feature = "check-loom")]
 use loom::sync::atomic::{AtomicPtr, AtomicU8, AtomicUsize, Ordering};
 
+#[cfg(feature = "check-loom")]
+use loom::thread_local;
+#[cfg(not(feature = "check-loom"))]
+use std::thread_local;
+
 use super::align;
-use super::atomic::Shared;
+use super::atomic::{Atomic, Shared};
+use super::membarrier;
 
 /// Per-thread array of hazard pointers.
 ///
-/// Caveat: a thread may have up to 8 hazard pointers.
+/// The first 8 slots are stored inline. When they are all occupied, the array grows by chaining
+/// another block of 8 slots. Blocks are only appended by the owner thread and never freed until the
+/// array is dropped, so that the other threads can scan the chain concurrently.
 #[derive(Debug)]
 pub struct LocalHazards {
     /// Bitmap that indicates the indices of occupied slots.
     occupied: AtomicU8,
 
     /// Array that contains the machine representation of hazard pointers without tag.
-    elements: [AtomicUsize; 8],
+    elements: [AtomicUsize; LocalHazards::SLOTS],
+
+    /// The next block of slots.
+    next: AtomicPtr<LocalHazards>,
 }
 
 impl Default for LocalHazards {
@@ -31,31 +41,49 @@ impl Default for LocalHazards {
         Self {
             occupied: Default::default(),
             elements: Default::default(),
+            next: AtomicPtr::new(ptr::null_mut()),
         }
     }
 }
 
 impl LocalHazards {
+    /// The number of slots in a block.
+    const SLOTS: usize = 8;
+
     /// Creates a hazard pointer array.
     pub fn new() -> Self {
         Self::default()
     }
 
-    /// Allocates a slot for a hazard pointer and returns its index. Returns `None` if the array is
-    /// full.
+    /// Allocates a slot for a hazard pointer and returns its index. Grows the array if it is full.
     ///
     /// # Safety
     ///
     /// This function must be called only by the thread that owns this hazard array.
-    pub unsafe fn alloc(&self, data: usize) -> Option<usize> {
-        let bitmap = self.occupied.load(Ordering::Relaxed);        
-        let pos = bitmap.leading_ones() as usize;
-        if pos == 8 {
-            return None;
+    pub unsafe fn alloc(&self, data: usize) -> usize {
+        let mut block = self;
+        let mut base = 0;
+        loop {
+            let bitmap = block.occupied.load(Ordering::Relaxed);
+            let pos = bitmap.leading_ones() as usize;
+            if pos != Self::SLOTS {
+                block.occupied.store(
+                    bitmap | ((1 << pos) as u8).reverse_bits(),
+                    Ordering::Release,
+                );
+                block.elements[pos].store(data, Ordering::Release);
+                return base + pos;
+            }
+
+            // Only the owner thread appends a block, so a plain store publishes it.
+            let mut next = block.next.load(Ordering::Relaxed);
+            if next.is_null() {
+                next = Box::into_raw(Box::new(LocalHazards::new()));
+                block.next.store(next, Ordering::Release);
+            }
+            block = &*next;
+            base += Self::SLOTS;
         }
-        self.occupied.store(bitmap | ((1 << pos) as u8).reverse_bits(), Ordering::Release);
-        self.elements[pos].store(data, Ordering::Release);
-        Some(pos)
     }
 
     /// Clears the hazard pointer at the given index.
@@ -65,7 +93,41 @@ impl LocalHazards {
     /// This function must be called only by the thread that owns this hazard array. The index must
     /// have been allocated.
     pub unsafe fn dealloc(&self, index: usize) {
-        self.occupied.fetch_sub(((1 << index) as u8).reverse_bits(), Ordering::Release);
+        let block = self.block(index);
+        block.occupied.fetch_sub(
+            ((1 << (index % Self::SLOTS)) as u8).reverse_bits(),
+            Ordering::Release,
+        );
+    }
+
+    /// Replaces the hazard pointer at the given index.
+    ///
+    /// # Safety
+    ///
+    /// This function must be called only by the thread that owns this hazard array. The index must
+    /// have been allocated.
+    pub unsafe fn set(&self, index: usize, data: usize) {
+        self.block(index).elements[index % Self::SLOTS].store(data, Ordering::Release);
+    }
+
+    /// Returns the block that contains the slot at the given index, which must have been allocated.
+    unsafe fn block(&self, index: usize) -> &Self {
+        let mut block = self;
+        for _ in 0..index / Self::SLOTS {
+            block = &*block.next.load(Ordering::Relaxed);
+        }
+        block
+    }
+
+    /// Returns the number of slots, occupied or not.
+    pub fn capacity(&self) -> usize {
+        let mut capacity = Self::SLOTS;
+        let mut block = self.next.load(Ordering::Acquire);
+        while let Some(b) = unsafe { block.as_ref() } {
+            capacity += Self::SLOTS;
+            block = b.next.load(Ordering::Acquire);
+        }
+        capacity
     }
 
     /// Returns an iterator of hazard pointers (with tags erased).
@@ -77,23 +139,37 @@ impl LocalHazards {
     }
 }
 
+impl Drop for LocalHazards {
+    fn drop(&mut self) {
+        let next = self.next.load(Ordering::Relaxed);
+        if !next.is_null() {
+            unsafe { drop(Box::from_raw(next)) };
+        }
+    }
+}
+
 #[derive(Debug)]
 struct LocalHazardsIter<'s> {
     hazards: &'s LocalHazards,
     occupied: u8,
 }
 
-impl Iterator for LocalHazardsIter<'_> {
+impl<'s> Iterator for LocalHazardsIter<'s> {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let pos = self.occupied.leading_zeros() as usize;
-        if pos == 8 {
-            None
-        } else {
-            let result = self.hazards.elements[pos].load(Ordering::Relaxed);
-            self.occupied ^= ((1 << pos) as u8).reverse_bits();
-            Some(result)
+        loop {
+            let pos = self.occupied.leading_zeros() as usize;
+            if pos != LocalHazards::SLOTS {
+                let result = self.hazards.elements[pos].load(Ordering::Relaxed);
+                self.occupied ^= ((1 << pos) as u8).reverse_bits();
+                return Some(result);
+            }
+
+            let next = self.hazards.next.load(Ordering::Acquire);
+            let next: &'s LocalHazards = unsafe { next.as_ref()? };
+            self.hazards = next;
+            self.occupied = next.occupied.load(Ordering::Acquire);
         }
     }
 }
@@ -103,23 +179,33 @@ pub struct Shield<'s, T> {
     data: usize, // preserves the tag of original `Shared`
     hazards: &'s LocalHazards,
     index: usize,
+    /// The set and the thread key of the record that should be released once its last shield is
+    /// dropped, if the record is not held by a `Registration`.
+    release: Option<(&'s Hazards, usize)>,
     _marker: PhantomData<&'s T>,
 }
 
 impl<'s, T> Shield<'s, T> {
-    /// Creates a new shield for hazard pointer. Returns `None` if the hazard array is fully
-    /// occupied.
+    /// Creates a new shield for hazard pointer.
     ///
     /// # Safety
     ///
     /// This function must be called only by the thread that owns this hazard array.
-    pub unsafe fn new(pointer: Shared<T>, hazards: &'s LocalHazards) -> Option<Self> {
-        hazards.alloc(pointer.with_tag(0).into_usize()).map(|index| Self {
+    pub unsafe fn new(pointer: Shared<T>, hazards: &'s LocalHazards) -> Self {
+        let index = hazards.alloc(pointer.with_tag(0).into_usize());
+        Self {
             data: pointer.into_usize(),
             hazards,
             index,
-            _marker: PhantomData
-        })
+            release: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Releases the record of the thread with `key` in `hazards` when the shield is dropped, unless
+    /// the record still has a hazard pointer by then.
+    pub(super) fn release_with(&mut self, hazards: &'s Hazards, key: usize) {
+        self.release = Some((hazards, key));
     }
 
     /// Returns `true` if the pointer is null.
@@ -165,11 +251,46 @@ impl<'s, T> Shield<'s, T> {
     pub fn validate(&self, pointer: Shared<T>) -> bool {
         self.shared().with_tag(0).into_usize() == pointer.with_tag(0).into_usize()
     }
+
+    /// Protects the pointer loaded from `atomic` instead, and returns it. The slot is reused, and
+    /// the protection is validated.
+    pub fn protect_from(&mut self, atomic: &Atomic<T>) -> Shared<T> {
+        let mut pointer = atomic.load(Ordering::Acquire);
+        loop {
+            self.set(pointer);
+            membarrier::light();
+            let current = atomic.load(Ordering::Acquire);
+            if self.validate(current) {
+                // The tag may have changed.
+                self.data = current.into_usize();
+                return current;
+            }
+            pointer = current;
+        }
+    }
+
+    /// Exchanges the pointers protected by the two shields, e.g., to move on from `curr` to `next`
+    /// in a hand-over-hand traversal.
+    pub fn swap(&mut self, other: &mut Self) {
+        mem::swap(self, other);
+    }
+
+    /// Protects `pointer` instead, reusing the slot. The protection must be validated after a fence.
+    fn set(&mut self, pointer: Shared<T>) {
+        unsafe {
+            self.hazards
+                .set(self.index, pointer.with_tag(0).into_usize())
+        };
+        self.data = pointer.into_usize();
+    }
 }
 
 impl<'s, T> Drop for Shield<'s, T> {
     fn drop(&mut self) {
         unsafe { self.hazards.dealloc(self.index) };
+        if let Some((hazards, key)) = self.release {
+            let _ = hazards.release(key);
+        }
     }
 }
 
@@ -185,22 +306,175 @@ impl<'s, T> fmt::Debug for Shield<'s, T> {
     }
 }
 
-/// Maps `ThreadId`s to their `Hazards`.
+/// A fixed number of shields that protect several pointers with a single fence.
+///
+/// The shields are indexed from zero, and can be exchanged with `swap`, e.g., to move on with the
+/// `prev`, `curr` and `next` pointers in a list traversal.
+///
+/// The number of shields is given at runtime instead of as a const parameter, since the pinned
+/// toolchain (Rust 1.47) doesn't support const generics.
+pub struct ShieldSet<'s, T> {
+    shields: Vec<Shield<'s, T>>,
+}
+
+impl<'s, T> ShieldSet<'s, T> {
+    /// Creates `len` shields that protect null pointers.
+    ///
+    /// # Safety
+    ///
+    /// This function must be called only by the thread that owns this hazard array.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is zero.
+    pub unsafe fn new(len: usize, hazards: &'s LocalHazards) -> Self {
+        assert!(len > 0, "empty shield set");
+        Self {
+            shields: (0..len)
+                .map(|_| Shield::new(Shared::null(), hazards))
+                .collect(),
+        }
+    }
+
+    /// Releases the record of the thread with `key` in `hazards` when the last shield is dropped.
+    pub(super) fn release_with(&mut self, hazards: &'s Hazards, key: usize) {
+        for shield in &mut self.shields {
+            shield.release_with(hazards, key);
+        }
+    }
+
+    /// Returns the number of shields.
+    pub fn len(&self) -> usize {
+        self.shields.len()
+    }
+
+    /// Returns `true` if there is no shield.
+    pub fn is_empty(&self) -> bool {
+        self.shields.is_empty()
+    }
+
+    /// Protects the pointers loaded from `atomics` with the first `atomics.len()` shields in order.
+    /// The protections are validated.
+    ///
+    /// A single fence is issued for all the pointers, and another one each time some of them fail
+    /// validation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more atomics than shields.
+    pub fn protect_from(&mut self, atomics: &[&Atomic<T>]) {
+        assert!(atomics.len() <= self.len(), "too many atomics to protect");
+        for (shield, atomic) in self.shields.iter_mut().zip(atomics) {
+            shield.set(atomic.load(Ordering::Acquire));
+        }
+        loop {
+            membarrier::light();
+            let mut validated = true;
+            for (shield, atomic) in self.shields.iter_mut().zip(atomics) {
+                let current = atomic.load(Ordering::Acquire);
+                if !shield.validate(current) {
+                    validated = false;
+                }
+                shield.set(current);
+            }
+            if validated {
+                return;
+            }
+        }
+    }
+
+    /// Exchanges the `a`-th and the `b`-th shields.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `a` or `b` are out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.shields.swap(a, b);
+    }
+
+    /// Returns an iterator over the shields.
+    pub fn iter(&self) -> impl Iterator<Item = &Shield<'s, T>> + '_ {
+        self.shields.iter()
+    }
+}
+
+impl<'s, T> Index<usize> for ShieldSet<'s, T> {
+    type Output = Shield<'s, T>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.shields[index]
+    }
+}
+
+impl<'s, T> IndexMut<usize> for ShieldSet<'s, T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.shields[index]
+    }
+}
+
+impl<'s, T> fmt::Debug for ShieldSet<'s, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.shields).finish()
+    }
+}
+
+/// Returns the key of the current thread, which is never reused by other threads.
+pub fn thread_key() -> usize {
+    // Keys are handed out on the first use, so they don't need to be modeled by loom.
+    static NEXT: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(1);
+
+    thread_local! {
+        static KEY: usize = NEXT.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    }
+
+    KEY.with(|key| *key)
+}
+
+/// Maps threads to their `LocalHazards`.
 ///
 /// Uses a hash table based on append-only lock-free linked list for simplicity. In practice, this
 /// is implemented using a more useful and efficient lock-free data structure.
+///
+/// Each record is owned by a thread, identified by its `thread_key`. A record is marked free when
+/// its thread is deregistered, and then reused by a new thread. Free records are skipped in scans.
 #[derive(Debug)]
 pub struct Hazards {
     heads: [AtomicPtr<Node>; Self::BUCKETS],
 }
 
+/// The record of a thread.
 #[derive(Debug)]
-struct Node {
+pub struct Node {
     next: AtomicPtr<Node>,
-    tid: ThreadId,
+    /// The key of the owner thread, or `Self::FREE` if the record is free.
+    owner: AtomicUsize,
+    /// The number of the holders that keep the record owned even if it has no hazard pointer. Only
+    /// accessed by the owner thread.
+    holders: AtomicUsize,
+    /// The number of the retired pointers buffered by the owner thread, reported by `stats`.
+    pending: AtomicUsize,
     hazards: LocalHazards,
 }
 
+impl Node {
+    const FREE: usize = 0;
+
+    /// Returns `true` if the record is owned by a thread.
+    fn is_active(&self) -> bool {
+        self.owner.load(Ordering::Acquire) != Self::FREE
+    }
+
+    /// Returns the hazard array of the owner thread.
+    pub fn hazards(&self) -> &LocalHazards {
+        &self.hazards
+    }
+
+    /// Sets the number of the retired pointers buffered by the owner thread.
+    pub fn set_pending(&self, pending: usize) {
+        self.pending.store(pending, Ordering::Relaxed);
+    }
+}
+
 impl Hazards {
     const BUCKETS: usize = 13;
 
@@ -247,40 +521,88 @@ impl Hazards {
             ],
         }
     }
-    pub fn get(&self, tid: ThreadId) -> &LocalHazards {
-        let index = {
-            let mut s = DefaultHasher::new();
-            tid.hash(&mut s);
-            (s.finish() as usize) % Self::BUCKETS
-        };
 
-        'start: loop {
-            let mut prev = unsafe { self.heads.get_unchecked(index) };
-            let mut cur = prev.load(Ordering::Acquire);
-            loop {
-                if cur.is_null() {
-                    let new = Box::into_raw(Box::new(Node {
-                        next: AtomicPtr::new(ptr::null_mut()),
-                        tid,
-                        hazards: LocalHazards::new(),
-                    }));
-                    if prev
-                        .compare_exchange(cur, new, Ordering::Release, Ordering::Relaxed)
-                        .is_ok()
-                    {
-                        return unsafe { &(*new).hazards };
-                    }
-                    unsafe { drop(Box::from_raw(new)) };
-                    continue 'start;
-                }
-                let cur_ref = unsafe { &*cur };
-                if cur_ref.tid == tid {
-                    return &cur_ref.hazards;
+    /// Returns the hazard array of the thread with the given key. Registers the thread with a free
+    /// or new record if the thread doesn't have one.
+    pub fn get(&self, key: usize) -> &LocalHazards {
+        &self.node(key).hazards
+    }
+
+    /// Returns the record of the thread with the given key, registered as `get`, and holds it until
+    /// `unhold`. A held record is not released even if it has no hazard pointer.
+    pub fn hold(&self, key: usize) -> &Node {
+        let node = self.node(key);
+        let _ = node.holders.fetch_add(1, Ordering::Relaxed);
+        node
+    }
+
+    /// Stops holding the record of the thread with the given key, and `release`s it.
+    pub fn unhold(&self, key: usize) -> bool {
+        let node = self.node(key);
+        let _ = node.holders.fetch_sub(1, Ordering::Relaxed);
+        self.release(key)
+    }
+
+    fn node(&self, key: usize) -> &Node {
+        debug_assert_ne!(key, Node::FREE);
+        let head = &self.heads[key % Self::BUCKETS];
+
+        let mut cur = head.load(Ordering::Acquire);
+        while let Some(cur_ref) = unsafe { cur.as_ref() } {
+            if cur_ref.owner.load(Ordering::Relaxed) == key {
+                return cur_ref;
+            }
+            cur = cur_ref.next.load(Ordering::Acquire);
+        }
+
+        // Acquires the slots cleared by the previous owner.
+        let mut cur = head.load(Ordering::Acquire);
+        while let Some(cur_ref) = unsafe { cur.as_ref() } {
+            if cur_ref
+                .owner
+                .compare_exchange(Node::FREE, key, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return cur_ref;
+            }
+            cur = cur_ref.next.load(Ordering::Acquire);
+        }
+
+        let new = Box::into_raw(Box::new(Node {
+            next: AtomicPtr::new(ptr::null_mut()),
+            owner: AtomicUsize::new(key),
+            holders: AtomicUsize::new(0),
+            pending: AtomicUsize::new(0),
+            hazards: LocalHazards::new(),
+        }));
+        let mut next = head.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*new).next.store(next, Ordering::Relaxed) };
+            match head.compare_exchange(next, new, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return unsafe { &*new },
+                Err(n) => next = n,
+            }
+        }
+    }
+
+    /// Deregisters the thread with the given key, so that its record is reused by a new thread.
+    /// Returns `false` if the record is held or still has a hazard pointer, in which case it is kept.
+    pub fn release(&self, key: usize) -> bool {
+        let mut cur = self.heads[key % Self::BUCKETS].load(Ordering::Acquire);
+        while let Some(cur_ref) = unsafe { cur.as_ref() } {
+            if cur_ref.owner.load(Ordering::Relaxed) == key {
+                // A shield may be leaked or moved to another thread.
+                if cur_ref.holders.load(Ordering::Relaxed) != 0
+                    || cur_ref.hazards.iter().next().is_some()
+                {
+                    return false;
                 }
-                prev = &cur_ref.next;
-                cur = prev.load(Ordering::Acquire);
+                cur_ref.owner.store(Node::FREE, Ordering::Release);
+                return true;
             }
+            cur = cur_ref.next.load(Ordering::Acquire);
         }
+        true
     }
 
     /// Returns all elements of `Hazards` for all threads. The tags are erased.
@@ -290,12 +612,79 @@ impl Hazards {
         for b in &self.heads {
             let mut cur = b.load(Ordering::Acquire);
             while let Some(cur_ref) = unsafe { cur.as_ref() } {
-                set.extend(cur_ref.hazards.iter());
+                if cur_ref.is_active() {
+                    set.extend(cur_ref.hazards.iter());
+                }
                 cur = cur_ref.next.load(Ordering::Acquire);
             }
         }
         set
     }
+
+    /// Returns the number of hazard pointer slots of all threads.
+    pub fn capacity(&self) -> usize {
+        let mut capacity = 0;
+        for b in &self.heads {
+            let mut cur = b.load(Ordering::Acquire);
+            while let Some(cur_ref) = unsafe { cur.as_ref() } {
+                if cur_ref.is_active() {
+                    capacity += cur_ref.hazards.capacity();
+                }
+                cur = cur_ref.next.load(Ordering::Acquire);
+            }
+        }
+        capacity
+    }
+
+    /// Returns the key, the pending count and the non-null hazard pointers of each registered
+    /// thread.
+    pub fn threads(&self) -> Vec<(usize, usize, Vec<usize>)> {
+        let mut threads = Vec::new();
+        for b in &self.heads {
+            let mut cur = b.load(Ordering::Acquire);
+            while let Some(cur_ref) = unsafe { cur.as_ref() } {
+                let owner = cur_ref.owner.load(Ordering::Acquire);
+                if owner != Node::FREE {
+                    let hazards = cur_ref.hazards.iter().filter(|&h| h != 0).collect();
+                    let pending = cur_ref.pending.load(Ordering::Relaxed);
+                    threads.push((owner, pending, hazards));
+                }
+                cur = cur_ref.next.load(Ordering::Acquire);
+            }
+        }
+        threads
+    }
+}
+
+/// Registration of the current thread to `Hazards`, which deregisters the thread when dropped.
+#[derive(Debug)]
+pub struct Registration<'s> {
+    hazards: &'s Hazards,
+    key: usize,
+    local: &'s LocalHazards,
+}
+
+impl<'s> Registration<'s> {
+    /// Registers the current thread.
+    pub fn new(hazards: &'s Hazards) -> Self {
+        let key = thread_key();
+        Self {
+            hazards,
+            key,
+            local: hazards.hold(key).hazards(),
+        }
+    }
+
+    /// Returns the hazard array of the current thread.
+    pub fn local(&self) -> &'s LocalHazards {
+        self.local
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let _ = self.hazards.unhold(self.key);
+    }
 }
 
 impl Drop for Hazards {
@@ -312,9 +701,10 @@ impl Drop for Hazards {
 #[cfg(test)]
 mod tests {
     use super::super::atomic::Owned;
-    use super::{Hazards, LocalHazards, Shield};
+    use super::{thread_key, Hazards, LocalHazards, Registration, Shield};
     use std::collections::HashSet;
     use std::mem;
+    use std::sync::atomic::Ordering::Relaxed;
     use std::sync::Arc;
     use std::thread;
 
@@ -327,7 +717,7 @@ mod tests {
         let hazards = LocalHazards::new();
         let shields = shareds
             .iter()
-            .map(|&s| unsafe { Shield::new(s, &hazards).unwrap() })
+            .map(|&s| unsafe { Shield::new(s, &hazards) })
             .collect::<Vec<_>>();
         let values = shields
             .iter()
@@ -341,6 +731,43 @@ mod tests {
             .for_each(|s| unsafe { drop(s.into_owned()) });
     }
 
+    #[test]
+    fn local_hazards_grow() {
+        const COUNT: usize = 100;
+
+        let shareds = (0..COUNT)
+            .map(|i| Owned::new(i).into_shared())
+            .collect::<Vec<_>>();
+        let hazards = LocalHazards::new();
+        let mut shields = shareds
+            .iter()
+            .map(|&s| unsafe { Shield::new(s, &hazards) })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hazards.iter().collect::<HashSet<_>>(),
+            shareds.iter().map(|s| s.into_usize()).collect()
+        );
+        assert_eq!(hazards.capacity(), 104);
+
+        // The freed slots in any block are reused.
+        let second_half = shields.split_off(COUNT / 2);
+        drop(shields);
+        assert_eq!(hazards.iter().count(), COUNT / 2);
+        let shields = shareds[..COUNT / 2]
+            .iter()
+            .map(|&s| unsafe { Shield::new(s, &hazards) })
+            .collect::<Vec<_>>();
+        assert!(shields.iter().all(|s| s.index < COUNT / 2));
+        assert_eq!(hazards.iter().count(), COUNT);
+
+        drop(second_half);
+        drop(shields);
+        assert_eq!(hazards.iter().count(), 0);
+        shareds
+            .into_iter()
+            .for_each(|s| unsafe { drop(s.into_owned()) });
+    }
+
     #[test]
     fn all_hazards() {
         let global_hazards = Arc::new(Hazards::new());
@@ -348,7 +775,7 @@ mod tests {
             .map(|i| {
                 let global_hazards = global_hazards.clone();
                 thread::spawn(move || {
-                    let hazards = global_hazards.get(thread::current().id());
+                    let hazards = global_hazards.get(thread_key());
                     let shared = Owned::new(i).into_shared();
                     mem::forget(unsafe { Shield::new(shared, &hazards) });
                     let data = shared.into_usize();
@@ -361,4 +788,54 @@ mod tests {
             .collect::<HashSet<_>>();
         assert_eq!(hazards, global_hazards.all_hazards())
     }
+
+    #[test]
+    fn hold_record() {
+        let hazards = Hazards::new();
+        let key = thread_key();
+        let node = hazards.hold(key);
+        node.set_pending(1);
+        // A held record is kept without hazard pointers.
+        assert!(!hazards.release(key));
+        assert_eq!(hazards.threads(), vec![(key, 1, Vec::new())]);
+        node.set_pending(0);
+        assert!(hazards.unhold(key));
+        assert!(hazards.threads().is_empty());
+    }
+
+    #[test]
+    fn reuse_released() {
+        let global_hazards = Arc::new(Hazards::new());
+        for _ in 0..(2 * Hazards::BUCKETS) {
+            let global_hazards = global_hazards.clone();
+            thread::spawn(move || {
+                let registration = Registration::new(&global_hazards);
+                let shared = Owned::new(0).into_shared();
+                let shield = unsafe { Shield::new(shared, registration.local()) };
+                assert_eq!(global_hazards.all_hazards().len(), 1);
+                drop(shield);
+                unsafe { drop(shared.into_owned()) }
+            })
+            .join()
+            .unwrap();
+        }
+
+        // The records of the exited threads are free and reused.
+        assert!(global_hazards.all_hazards().is_empty());
+        assert_eq!(global_hazards.capacity(), 0);
+        let records = global_hazards
+            .heads
+            .iter()
+            .map(|head| {
+                let mut count = 0;
+                let mut cur = head.load(Relaxed);
+                while let Some(cur_ref) = unsafe { cur.as_ref() } {
+                    count += 1;
+                    cur = cur_ref.next.load(Relaxed);
+                }
+                count
+            })
+            .sum::<usize>();
+        assert!(records <= Hazards::BUCKETS);
+    }
 }
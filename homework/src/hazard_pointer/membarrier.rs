@@ -0,0 +1,130 @@
+//! Asymmetric fences.
+//!
+//! The SC fences of hazard pointers are split into a light fence for the readers and a heavy fence
+//! for the reclaimers. If asymmetric fences are enabled, the light fence is a compiler fence, and
+//! the heavy fence is an SC fence followed by `membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED)`, which
+//! executes a full fence on all the running threads of the process. Otherwise, both are SC fences.
+//!
+//! The mode is decided once by `enable`, or by the first light or heavy fence, and never changes
+//! afterwards, so that the readers and the reclaimers always agree on it.
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{compiler_fence, fence, AtomicU8, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{fence, Ordering};
+
+#[cfg(not(feature = "check-loom"))]
+const UNDECIDED: u8 = 0;
+#[cfg(not(feature = "check-loom"))]
+const SYMMETRIC: u8 = 1;
+#[cfg(not(feature = "check-loom"))]
+const ASYMMETRIC: u8 = 2;
+
+#[cfg(not(feature = "check-loom"))]
+/// The fence mode of the process.
+static MODE: AtomicU8 = AtomicU8::new(UNDECIDED);
+
+#[cfg(not(feature = "check-loom"))]
+/// Returns the fence mode, deciding on symmetric fences if not decided yet.
+fn mode() -> u8 {
+    let mode = MODE.load(Ordering::Relaxed);
+    if mode != UNDECIDED {
+        return mode;
+    }
+    // A failed CAS reads the latest mode, so all threads agree on it.
+    match MODE.compare_exchange(UNDECIDED, SYMMETRIC, Ordering::Relaxed, Ordering::Relaxed) {
+        Ok(_) => SYMMETRIC,
+        Err(mode) => mode,
+    }
+}
+
+#[cfg(not(feature = "check-loom"))]
+/// Tries to use asymmetric fences, and returns whether they are used.
+pub fn enable() -> bool {
+    if MODE.load(Ordering::Relaxed) == UNDECIDED && sys::register() {
+        let _ = MODE.compare_exchange(UNDECIDED, ASYMMETRIC, Ordering::Relaxed, Ordering::Relaxed);
+    }
+    mode() == ASYMMETRIC
+}
+
+#[cfg(not(feature = "check-loom"))]
+/// The fence executed by the readers between announcing and validating a hazard pointer.
+#[inline]
+pub fn light() {
+    if mode() == ASYMMETRIC {
+        compiler_fence(Ordering::SeqCst);
+    } else {
+        fence(Ordering::SeqCst);
+    }
+}
+
+#[cfg(not(feature = "check-loom"))]
+/// The fence executed by the reclaimers between retiring pointers and scanning hazard pointers.
+pub fn heavy() {
+    fence(Ordering::SeqCst);
+    if mode() == ASYMMETRIC {
+        sys::barrier();
+    }
+}
+
+// Loom doesn't model `membarrier`, so the fences are always symmetric.
+
+#[cfg(feature = "check-loom")]
+/// Tries to use asymmetric fences, and returns whether they are used.
+pub fn enable() -> bool {
+    false
+}
+
+#[cfg(feature = "check-loom")]
+/// The fence executed by the readers between announcing and validating a hazard pointer.
+pub fn light() {
+    fence(Ordering::SeqCst);
+}
+
+#[cfg(feature = "check-loom")]
+/// The fence executed by the reclaimers between retiring pointers and scanning hazard pointers.
+pub fn heavy() {
+    fence(Ordering::SeqCst);
+}
+
+#[cfg(all(target_os = "linux", not(feature = "check-loom")))]
+mod sys {
+    // See `membarrier(2)`.
+    const MEMBARRIER_CMD_QUERY: libc::c_int = 0;
+    const MEMBARRIER_CMD_PRIVATE_EXPEDITED: libc::c_int = 1 << 3;
+    const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED: libc::c_int = 1 << 4;
+
+    fn membarrier(cmd: libc::c_int) -> libc::c_long {
+        unsafe { libc::syscall(libc::SYS_membarrier, cmd, 0) }
+    }
+
+    /// Registers the process for expedited private barriers. Returns `false` if not supported.
+    pub fn register() -> bool {
+        let supported = membarrier(MEMBARRIER_CMD_QUERY);
+        supported >= 0
+            && supported & libc::c_long::from(MEMBARRIER_CMD_PRIVATE_EXPEDITED) != 0
+            && membarrier(MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED) == 0
+    }
+
+    /// Executes a full fence on all the running threads of the process.
+    pub fn barrier() {
+        // It never fails once registered.
+        assert_eq!(
+            membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED),
+            0,
+            "membarrier failed"
+        );
+    }
+}
+
+#[cfg(all(not(target_os = "linux"), not(feature = "check-loom")))]
+mod sys {
+    /// Asymmetric fences are not supported.
+    pub fn register() -> bool {
+        false
+    }
+
+    pub fn barrier() {
+        unreachable!()
+    }
+}
@@ -60,77 +60,267 @@
 //! another SC fence. If we insert a SC fence between `T1-1` and `T1-2`, and another between `T2-1`
 //! and `T2-2`, then either `T1's fence → T2's fence` or `T2's fence → T1's fence` holds.
 //! Therefore, `T1-1 → T2-2` or `T2-1 → T1-2`.
+//!
+//! # Asymmetric Fences
+//!
+//! `T1`'s fence is executed on every protection, whereas `T2`'s fence is executed once per scan of
+//! many retired pointers. So it pays off to make `T1`'s fence cheaper at the cost of `T2`'s. With
+//! `enable_asymmetric_fences`, `T1` only issues a compiler fence, which keeps `T1-1` before `T1-2`
+//! in the generated code but doesn't order them in the hardware. Instead, `T2` issues an SC fence
+//! followed by `membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED)`, which makes every running thread of
+//! the process execute a full fence at some point `p` during the syscall. The threads that are not
+//! running execute one when they are switched out or in.
+//!
+//! So `T1`'s full fence at `p` plays the role of `T1`'s SC fence, but at an arbitrary point of its
+//! execution. If `p` is after `T1-1`, then `T1-1 → T2-2`. Otherwise, `p` is before `T1-2`, and
+//! `T2-1 → T1-2`, since `T2`'s SC fence is before the syscall returns. In either case, the argument
+//! above holds.
+//!
+//! The mode is decided once, on the first protection or scan, so that the readers never skip the
+//! SC fence while a reclaimer does.
 
 use core::cell::RefCell;
-use std::thread;
+use core::ptr;
 
 #[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{fence, Ordering};
+use core::sync::atomic::Ordering;
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{fence, Ordering};
+use loom::sync::atomic::Ordering;
 
 #[cfg(feature = "check-loom")]
 use loom::thread_local;
 #[cfg(not(feature = "check-loom"))]
 use std::thread_local;
 
-mod align;
+pub(crate) mod align;
 mod atomic;
-mod hazard;
-mod retire;
+mod domain;
+pub(crate) mod hazard;
+mod membarrier;
+pub mod queue;
+mod reclaim;
+pub(crate) mod retire;
+pub mod stack;
+mod stats;
 
-pub use atomic::{Atomic, Owned, Shared};
-use hazard::Hazards;
-pub use hazard::Shield;
-use retire::Retirees;
+pub use atomic::{Atomic, CompareExchangeError, Owned, Pointer, Shared};
+pub use domain::Domain;
+pub use hazard::{thread_key, Shield, ShieldSet};
+use hazard::{Hazards, LocalHazards, Registration};
+pub use queue::Queue;
+pub use reclaim::{HazardPointer, HazardShield};
+pub use retire::Retire;
+use retire::{Free, Retired, Retirees};
+pub use stack::{PopAll, Stack};
+pub use stats::{Stats, ThreadStats};
 
 #[cfg(not(feature = "check-loom"))]
-/// Global set of all hazard pointers.
-pub static HAZARDS: Hazards = Hazards::new();
+/// The default global domain.
+static DEFAULT_DOMAIN: Domain = Domain::new();
+
+#[cfg(not(feature = "check-loom"))]
+/// Global set of all hazard pointers of the default domain.
+pub static HAZARDS: &Hazards = &DEFAULT_DOMAIN.hazards;
 
 #[cfg(feature = "check-loom")]
 loom::lazy_static! {
-    /// Global set of all hazard pointers.
-    pub static ref HAZARDS: Hazards = Hazards::new();
+    /// The default global domain.
+    static ref DEFAULT_DOMAIN: Domain = Domain::new();
+
+    /// Global set of all hazard pointers of the default domain.
+    pub static ref HAZARDS: &'static Hazards = &DEFAULT_DOMAIN.hazards;
 }
 
 thread_local! {
-    /// Thread-local list of retired pointers. The first element of the pair is the machine
-    /// representation of the pointer and the second is the function pointer to `free::<T>`.
-    static RETIRED: RefCell<Retirees<'static>> = RefCell::new(Retirees::new(&HAZARDS));
+    /// Registration of the current thread to the default domain. The thread's hazard array is
+    /// reused by a new thread after the thread exits.
+    static REGISTRATION: Registration<'static> = Registration::new(&DEFAULT_DOMAIN.hazards);
+
+    /// Thread-local list of retired pointers of the default domain.
+    static RETIRED: RefCell<Retirees<'static>> = RefCell::new(Retirees::new(&DEFAULT_DOMAIN));
+}
+
+/// Returns the default global domain.
+pub fn default_domain() -> &'static Domain {
+    &DEFAULT_DOMAIN
 }
 
-/// Returns `None` if the current thread's hazard array is fully occupied. The returned shield must
-/// be validated before using.
+/// Tries to replace the SC fences of hazard pointers with asymmetric fences, and returns whether
+/// they are used. See the module documentation for the details.
+///
+/// This is supported only on Linux with `membarrier(2)`, and otherwise the SC fences are kept. The
+/// fences are fixed on the first protection or scan, so this must be called before them, e.g., at
+/// the start of `main`. Once enabled, the asymmetric fences are used by all domains.
+pub fn enable_asymmetric_fences() -> bool {
+    membarrier::enable()
+}
+
+/// Returns a shield that protects `pointer` in the default domain. The returned shield must be
+/// validated before using.
+///
+/// The current thread's hazard array grows as needed, so this always returns `Some`.
 pub fn protect<T>(pointer: Shared<T>) -> Option<Shield<'static, T>> {
-    let local_hz = HAZARDS.get(thread::current().id());
-    let shield = unsafe { Shield::new(pointer, local_hz) };
-    fence(Ordering::SeqCst);
-    shield
+    protect_in(default_domain(), pointer)
 }
 
-/// Returns a validated shield. Returns `None` if the current thread's hazard array is fully
-/// occupied.
+/// Returns a validated shield in the default domain.
+///
+/// The current thread's hazard array grows as needed, so this always returns `Some`.
 pub fn get_protected<T>(atomic: &Atomic<T>) -> Option<Shield<'static, T>> {
-    loop {
-        if let Some(shield) = protect(atomic.load(Ordering::Acquire)) {
-            if shield.validate(atomic.load(Ordering::Acquire)) {
-                return Some(shield);
-            }
-        } else {
-            return None;
-        }
-    }
-    
+    get_protected_in(default_domain(), atomic)
+}
+
+/// Returns `len` shields in the default domain, which protect null pointers at first.
+///
+/// # Panics
+///
+/// Panics if `len` is zero.
+pub fn shield_set<T>(len: usize) -> ShieldSet<'static, T> {
+    shield_set_in(default_domain(), len)
 }
 
-/// Retires a pointer.
+/// Retires a pointer to the default domain.
 pub fn retire<T>(pointer: Shared<T>) {
-    RETIRED.with(|r| r.borrow_mut().retire(pointer));
+    retire_with(pointer, Free);
 }
 
-/// Frees the pointers that are `retire`d by the current thread and not `protect`ed by any other
-/// threads.
+/// Retires a pointer to the default domain, which will be reclaimed by `deleter`.
+pub fn retire_with<T, D: Retire<T>>(pointer: Shared<T>, deleter: D) {
+    RETIRED.with(|r| r.borrow_mut().retire(pointer, deleter));
+}
+
+/// Frees the pointers that are `retire`d to the default domain by the current thread and not
+/// `protect`ed by any other threads.
 pub fn collect() {
     RETIRED.with(|r| r.borrow_mut().collect());
 }
+
+/// Returns a snapshot of the reclamation statistics of the default domain.
+pub fn stats() -> Stats {
+    default_domain().stats()
+}
+
+/// Returns a shield that protects `pointer` in `domain`. The returned shield must be validated
+/// before using.
+///
+/// The current thread's hazard array grows as needed, so this always returns `Some`.
+pub fn protect_in<'d, T>(domain: &'d Domain, pointer: Shared<T>) -> Option<Shield<'d, T>> {
+    let (hazards, registered) = local_hazards(domain);
+    let mut shield = unsafe { Shield::new(pointer, hazards) };
+    if !registered {
+        shield.release_with(&domain.hazards, thread_key());
+    }
+    membarrier::light();
+    Some(shield)
+}
+
+/// Returns a validated shield in `domain`.
+///
+/// The current thread's hazard array grows as needed, so this always returns `Some`.
+pub fn get_protected_in<'d, T>(domain: &'d Domain, atomic: &Atomic<T>) -> Option<Shield<'d, T>> {
+    loop {
+        let shield = protect_in(domain, atomic.load(Ordering::Acquire))?;
+        if shield.validate(atomic.load(Ordering::Acquire)) {
+            return Some(shield);
+        }
+    }
+}
+
+/// Returns `len` shields in `domain`, which protect null pointers at first.
+///
+/// # Panics
+///
+/// Panics if `len` is zero.
+pub fn shield_set_in<T>(domain: &Domain, len: usize) -> ShieldSet<'_, T> {
+    let (hazards, registered) = local_hazards(domain);
+    let mut shields = unsafe { ShieldSet::new(len, hazards) };
+    if !registered {
+        shields.release_with(&domain.hazards, thread_key());
+    }
+    shields
+}
+
+/// Returns the current thread's hazard array in `domain`, and whether it is held by the thread's
+/// registration. If not, its record should be released once its shields are dropped, so that the
+/// records of exited threads are reused.
+fn local_hazards(domain: &Domain) -> (&LocalHazards, bool) {
+    if ptr::eq(domain, default_domain()) {
+        // The registration may be already destroyed if the current thread is exiting.
+        if let Ok(local) = REGISTRATION.try_with(|r| r.local()) {
+            return (local, true);
+        }
+    }
+    (domain.hazards.get(thread_key()), false)
+}
+
+/// Retires a pointer to `domain`.
+pub fn retire_in<T>(domain: &Domain, pointer: Shared<T>) {
+    retire_with_in(domain, pointer, Free);
+}
+
+/// Retires a pointer to `domain`, which will be reclaimed by `deleter`.
+pub fn retire_with_in<T, D: Retire<T>>(domain: &Domain, pointer: Shared<T>, deleter: D) {
+    if ptr::eq(domain, default_domain()) {
+        return retire_with(pointer, deleter);
+    }
+    domain.retired.push(vec![Retired::new(pointer, deleter)]);
+    domain.counters.retire();
+    if domain.retired.count() > domain.threshold.load(Ordering::Relaxed) {
+        collect_in(domain);
+    }
+}
+
+/// Frees the pointers that are `retire`d to `domain` and not `protect`ed by any other threads.
+pub fn collect_in(domain: &Domain) {
+    if ptr::eq(domain, default_domain()) {
+        return collect();
+    }
+    // Dropping the retirees scans the domain once and hands the survivors back to it.
+    #[cfg(not(feature = "check-loom"))]
+    drop(Retirees::new(domain));
+    #[cfg(feature = "check-loom")]
+    {
+        let mut retirees = Retirees::new(domain);
+        retirees.collect();
+        retirees.flush();
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+/// Checks that a stalled reader holds only the nodes it protects.
+///
+/// A reader protects nodes with `protect` and stalls, while a writer pushes and pops each index
+/// with `push_pop`. The nodes popped by the writer but the protected ones are freed once the
+/// thread-local list is full. Finally the reader checks the protected nodes with `check`.
+fn stalled_reader_bounded<S>(
+    protect: impl FnOnce() -> S + Send,
+    check: impl FnOnce(S) + Send,
+    push_pop: impl Fn(usize) + Send,
+) {
+    use crossbeam_utils::thread::scope;
+    use std::sync::mpsc;
+
+    const ITER: usize = 1024 * 16;
+
+    let (protected_send, protected_recv) = mpsc::channel();
+    let (done_send, done_recv) = mpsc::channel::<()>();
+    scope(|s| {
+        let _ = s.spawn(move |_| {
+            let protected = protect();
+            protected_send.send(()).unwrap();
+            done_recv.recv().unwrap();
+            check(protected);
+        });
+
+        let _ = s.spawn(move |_| {
+            protected_recv.recv().unwrap();
+            for i in 0..ITER {
+                push_pop(i);
+                let threshold = default_domain().threshold.load(Ordering::Relaxed);
+                assert!(RETIRED.with(|r| r.borrow().len()) <= threshold);
+            }
+            done_send.send(()).unwrap();
+        });
+    })
+    .unwrap();
+}
@@ -0,0 +1,357 @@
+//! Michael-Scott lock-free queue protected by hazard pointers.
+//!
+//! Michael and Scott.  Simple, Fast, and Practical Non-Blocking and Blocking Concurrent Queue
+//! Algorithms.  PODC 1996.  http://dl.acm.org/citation.cfm?id=248106
+
+use core::mem::MaybeUninit;
+use core::ptr;
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::Ordering;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::Ordering;
+
+use crossbeam_utils::CachePadded;
+
+use super::{get_protected, retire, Atomic, Owned, Shared};
+
+/// Michael-Scott queue.
+///
+/// Usable with any number of producers and consumers. Popped nodes are reclaimed by hazard
+/// pointers, so a stalled thread holds at most the nodes it is accessing.
+///
+/// # Example
+///
+/// ```
+/// use cs492_concur_homework::hazard_pointer::Queue;
+///
+/// let queue = Queue::new();
+/// queue.push(1);
+/// queue.push(2);
+/// assert_eq!(queue.try_pop(), Some(1));
+/// assert_eq!(queue.try_pop(), Some(2));
+/// assert!(queue.is_empty());
+/// ```
+// The representation here is a singly-linked list, with a sentinel node at the front. In general
+// the `tail` pointer may lag behind the actual tail.
+#[derive(Debug)]
+pub struct Queue<T> {
+    head: CachePadded<Atomic<Node<T>>>,
+    tail: CachePadded<Atomic<Node<T>>>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    /// The slot in which a value of type `T` can be stored.
+    ///
+    /// The sentinel node never contains a value, and the other nodes contain a value until it gets
+    /// popped out.
+    data: MaybeUninit<T>,
+
+    next: Atomic<Node<T>>,
+}
+
+// Any particular `T` should never be accessed concurrently, so no need for `Sync`.
+unsafe impl<T: Send> Sync for Queue<T> {}
+unsafe impl<T: Send> Send for Queue<T> {}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        let sentinel = Owned::new(Node {
+            data: MaybeUninit::uninit(),
+            next: Atomic::null(),
+        })
+        .into_shared();
+        Self {
+            head: CachePadded::new(Atomic::from(sentinel)),
+            tail: CachePadded::new(Atomic::from(sentinel)),
+        }
+    }
+}
+
+impl<T: 'static> Queue<T> {
+    /// Creates a new, empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `t` to the back of the queue.
+    pub fn push(&self, t: T) {
+        let new = Owned::new(Node {
+            data: MaybeUninit::new(t),
+            next: Atomic::null(),
+        })
+        .into_shared();
+
+        loop {
+            // We push onto the tail, so we'll start optimistically by looking there first. `tail`
+            // never lags behind `head`, so it is not retired while it is protected and still the
+            // tail.
+            let tail = get_protected(&self.tail).unwrap();
+
+            // Attempt to push onto the `tail` snapshot; fails if `tail.next` has changed.
+            let tail_ref = unsafe { tail.deref() };
+            let next = tail_ref.next.load(Ordering::Acquire);
+
+            // If `tail` is not the actual tail, try to "help" by moving the tail pointer forward.
+            if !next.is_null() {
+                let _ = self.tail.compare_and_set(
+                    tail.shared(),
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                );
+                continue;
+            }
+
+            // looks like the actual tail; attempt to link at `tail.next`.
+            if tail_ref
+                .next
+                .compare_and_set(Shared::null(), new, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                // try to move the tail pointer forward.
+                let _ = self.tail.compare_and_set(
+                    tail.shared(),
+                    new,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                );
+                break;
+            }
+        }
+    }
+
+    /// Attempts to dequeue from the front.
+    ///
+    /// Returns `None` if the queue is observed to be empty.
+    pub fn try_pop(&self) -> Option<T> {
+        loop {
+            let head = get_protected(&self.head).unwrap();
+            let h = unsafe { head.deref() };
+            let next = get_protected(&h.next).unwrap();
+
+            // `next` is retired only after `head` is, so if `head` is still the head, then `next`
+            // was not retired when protected.
+            if head.shared().into_usize() != self.head.load(Ordering::Acquire).into_usize() {
+                continue;
+            }
+            let next_ref = unsafe { next.as_ref()? };
+
+            // Moves `tail` if it's stale. Relaxed load is enough because if tail == head, then the
+            // messages for that node are already acquired.
+            let tail = self.tail.load(Ordering::Relaxed);
+            if tail.into_usize() == head.shared().into_usize() {
+                let _ = self.tail.compare_and_set(
+                    tail,
+                    next.shared(),
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                );
+            }
+
+            if self
+                .head
+                .compare_and_set(
+                    head.shared(),
+                    next.shared(),
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                // The node is still protected by `head` after it is retired, and `next` becomes the
+                // new sentinel.
+                retire(head.shared());
+                return Some(unsafe { ptr::read(next_ref.data.as_ptr()) });
+            }
+        }
+    }
+
+    /// Dequeues from the front, spinning until an element is pushed if the queue is empty.
+    pub fn pop(&self) -> T {
+        loop {
+            if let Some(t) = self.try_pop() {
+                return t;
+            }
+        }
+    }
+
+    /// Returns `true` if the queue is observed to be empty.
+    pub fn is_empty(&self) -> bool {
+        let head = get_protected(&self.head).unwrap();
+        unsafe { head.deref() }
+            .next
+            .load(Ordering::Acquire)
+            .is_null()
+    }
+
+    /// Returns an iterator that pops elements until the queue is observed to be empty.
+    pub fn drain(&self) -> Drain<'_, T> {
+        Drain { queue: self }
+    }
+}
+
+/// A draining iterator of a [`Queue`], created by [`Queue::drain`].
+#[derive(Debug)]
+pub struct Drain<'a, T> {
+    queue: &'a Queue<T>,
+}
+
+impl<T: 'static> Iterator for Drain<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.queue.try_pop()
+    }
+}
+
+/// An owning iterator of a [`Queue`], which yields the elements from front to back.
+#[derive(Debug)]
+pub struct IntoIter<T> {
+    queue: Queue<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // The queue is owned, so the old sentinel is freed right away, and the node of the popped
+        // value becomes the new sentinel. The rest is freed by `Queue::drop`.
+        let head = self.queue.head.load(Ordering::Relaxed);
+        let next = unsafe { head.deref() }.next.load(Ordering::Relaxed);
+        if next.is_null() {
+            return None;
+        }
+        if self.queue.tail.load(Ordering::Relaxed).into_usize() == head.into_usize() {
+            self.queue.tail.store(next, Ordering::Relaxed);
+        }
+        self.queue.head.store(next, Ordering::Relaxed);
+        unsafe {
+            drop(head.into_owned());
+            Some(ptr::read(next.deref().data.as_ptr()))
+        }
+    }
+}
+
+impl<T> IntoIterator for Queue<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { queue: self }
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        unsafe {
+            // The first node is the sentinel, and the others contain values.
+            let sentinel = self.head.load(Ordering::Relaxed).into_owned();
+            let mut node = sentinel.next.load(Ordering::Relaxed);
+            while !node.is_null() {
+                let mut n = node.into_owned();
+                ptr::drop_in_place(n.data.as_mut_ptr());
+                node = n.next.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::super::get_protected;
+    use super::Queue;
+    use crossbeam_utils::thread::scope;
+    use std::sync::atomic::Ordering::Relaxed;
+
+    #[test]
+    fn push_try_pop() {
+        let q = Queue::new();
+        assert!(q.is_empty());
+        for i in 0..200 {
+            q.push(i);
+        }
+        assert!(!q.is_empty());
+        for i in 0..200 {
+            assert_eq!(q.try_pop(), Some(i));
+        }
+        assert!(q.is_empty());
+        assert!(q.try_pop().is_none());
+    }
+
+    #[test]
+    fn push_try_pop_spmc() {
+        const COUNT: usize = 1024 * 16;
+
+        let q = Queue::new();
+        scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|_| {
+                    let mut cur = None;
+                    for _ in 0..COUNT {
+                        if let Some(elem) = q.try_pop() {
+                            assert!(cur < Some(elem));
+                            cur = Some(elem);
+                        }
+                    }
+                });
+            }
+
+            s.spawn(|_| {
+                for i in 0..COUNT {
+                    q.push(i);
+                }
+            });
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn pop_drain_into_iter() {
+        let q = Queue::new();
+        for i in 0..6 {
+            q.push(i);
+        }
+        assert_eq!(q.pop(), 0);
+        assert_eq!(q.drain().take(2).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(q.into_iter().collect::<Vec<_>>(), [3, 4, 5]);
+    }
+
+    #[test]
+    fn drop_values() {
+        let q = Queue::new();
+        for i in 0..100 {
+            q.push(Box::new(i));
+        }
+        assert_eq!(q.try_pop().map(|b| *b), Some(0));
+        // The remaining boxes are freed by `Queue::drop`.
+    }
+
+    #[test]
+    fn stalled_reader_bounded() {
+        let q = Queue::new();
+        q.push(0);
+        super::super::stalled_reader_bounded(
+            // The reader stalls while protecting the sentinel and the front node.
+            || {
+                let head = get_protected(&q.head).unwrap();
+                let next = get_protected(&unsafe { head.deref() }.next).unwrap();
+                (head, next)
+            },
+            // The nodes are not freed even though they were popped long ago.
+            |(head, next)| {
+                let link = unsafe { head.deref() }.next.load(Relaxed);
+                assert_eq!(link.into_usize(), next.shared().into_usize());
+                assert_eq!(unsafe { next.deref().data.assume_init() }, 0);
+            },
+            |i| {
+                if i == 0 {
+                    assert_eq!(q.try_pop(), Some(0));
+                }
+                q.push(i);
+                assert_eq!(q.try_pop(), Some(i));
+            },
+        );
+    }
+}
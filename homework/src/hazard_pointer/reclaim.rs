@@ -0,0 +1,55 @@
+//! Hazard pointers as a reclamation scheme of the `lockfree` data structures.
+
+// The links of the `lockfree` data structures are not modeled by loom.
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use lockfree::Reclaim;
+
+use super::{protect, retire, Shared, Shield};
+
+/// Hazard pointer based reclamation.
+///
+/// Each shield occupies a hazard pointer slot of the current thread while it protects a pointer.
+///
+/// # Example
+///
+/// ```
+/// use cs492_concur_homework::hazard_pointer::HazardPointer;
+/// use lockfree::Stack;
+///
+/// let stack = Stack::<usize, HazardPointer>::default();
+/// stack.push(1);
+/// assert_eq!(stack.pop(), Some(1));
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HazardPointer;
+
+/// The shield of [`HazardPointer`].
+#[derive(Debug, Default)]
+pub struct HazardShield(Option<Shield<'static, u8>>);
+
+impl Reclaim for HazardPointer {
+    type Guard = ();
+    type Shield = HazardShield;
+
+    fn pin() {}
+
+    fn protect<T>(src: &AtomicPtr<T>, shield: &mut HazardShield, _: &()) -> *mut T {
+        let mut pointer = src.load(Ordering::Acquire);
+        loop {
+            // Releases the slot first, so that it can be reused.
+            shield.0 = None;
+            shield.0 = protect(Shared::<u8>::from_usize(pointer as usize));
+
+            let current = src.load(Ordering::Acquire);
+            if current == pointer {
+                return pointer;
+            }
+            pointer = current;
+        }
+    }
+
+    unsafe fn retire<T>(ptr: *mut T, _: &()) {
+        retire(Shared::<T>::from_usize(ptr as usize));
+    }
+}
@@ -1,59 +1,293 @@
+use core::cmp;
+use core::mem;
+use core::ptr;
 #[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{fence, Ordering};
-use std::collections::HashSet;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{fence, Ordering};
+use loom::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::time::Instant;
 
 use super::align;
 use super::atomic::Shared;
-use super::hazard::Hazards;
+use super::domain::Domain;
+use super::hazard::{thread_key, Hazards, Node};
+use super::membarrier;
 
-/// Thread-local list of retired pointers.
+/// Custom reclamation of retired objects of type `T`.
+///
+/// Implemented for closures taking the pointer to the object. The deleter may be run by another
+/// thread that reclaims the object.
+///
+/// # Example
+///
+/// ```
+/// use cs492_concur_homework::hazard_pointer::{retire_with, Owned};
+///
+/// let shared = Owned::new(1).into_shared();
+/// retire_with(shared, |pointer: *mut i32| unsafe { drop(Box::from_raw(pointer)) });
+/// ```
+pub trait Retire<T>: Send + 'static {
+    /// Reclaims the object pointed to by `pointer`.
+    ///
+    /// # Safety
+    ///
+    /// `pointer` should be retired and no longer accessed by any thread.
+    unsafe fn reclaim(self, pointer: *mut T);
+}
+
+impl<T, F: FnOnce(*mut T) + Send + 'static> Retire<T> for F {
+    unsafe fn reclaim(self, pointer: *mut T) {
+        self(pointer)
+    }
+}
+
+/// The default deleter, which frees an object allocated by `Owned`.
+#[derive(Debug, Clone, Copy)]
+pub struct Free;
+
+impl<T> Retire<T> for Free {
+    unsafe fn reclaim(self, pointer: *mut T) {
+        drop(Box::from_raw(pointer))
+    }
+}
+
+/// A retired pointer with its deleter.
+#[derive(Debug)]
+pub struct Retired {
+    /// The machine representation of a pointer without tag.
+    pointer: usize,
+    /// The deleter of type `D`, erased to a raw pointer. It doesn't allocate if `D` is zero-sized.
+    /// Null once the deleter is taken by `reclaim`.
+    deleter: *mut u8,
+    /// The function pointer to `reclaim::<T, D>` where `T` is the type of the object.
+    reclaim: unsafe fn(usize, *mut u8),
+    /// The function pointer to `drop_deleter::<D>`, which frees the deleter without running it.
+    drop_deleter: unsafe fn(*mut u8),
+}
+
+impl Retired {
+    /// Creates a retired pointer that will be reclaimed by `deleter`.
+    pub fn new<T, D: Retire<T>>(pointer: Shared<T>, deleter: D) -> Self {
+        unsafe fn reclaim<T, D: Retire<T>>(data: usize, deleter: *mut u8) {
+            debug_assert_eq!(align::decompose_tag::<T>(data).1, 0);
+            Box::from_raw(deleter as *mut D).reclaim(data as *mut T)
+        }
+        unsafe fn drop_deleter<D>(deleter: *mut u8) {
+            drop(Box::from_raw(deleter as *mut D))
+        }
+        Self {
+            pointer: pointer.with_tag(0).into_usize(),
+            deleter: Box::into_raw(Box::new(deleter)) as *mut u8,
+            reclaim: reclaim::<T, D>,
+            drop_deleter: drop_deleter::<D>,
+        }
+    }
+
+    /// Reclaims the object with the deleter.
+    ///
+    /// # Safety
+    ///
+    /// The pointer should be no longer accessed by any thread.
+    pub unsafe fn reclaim(mut self) {
+        let deleter = mem::replace(&mut self.deleter, ptr::null_mut());
+        (self.reclaim)(self.pointer, deleter)
+    }
+}
+
+impl Drop for Retired {
+    fn drop(&mut self) {
+        // Dropped without `reclaim`: the object is leaked, but the deleter is freed.
+        if !self.deleter.is_null() {
+            unsafe { (self.drop_deleter)(self.deleter) };
+        }
+    }
+}
+
+/// List of retired pointers shared by the threads of a domain.
+///
+/// It holds the retired pointers left by exited threads, and the ones retired to a domain without
+/// thread-local buffering. Threads push retired pointers as a batch and adopt all the batches in
+/// `Retirees::collect`. The batches are only pushed and taken all at once, so the list is a Treiber
+/// stack free from ABA.
+///
+/// The list is generic over the retired items, so that the other schemes reuse it. It doesn't
+/// reclaim the items on drop, which is left to the owner.
+#[derive(Debug)]
+pub struct SharedRetirees<R = Retired> {
+    head: AtomicPtr<Batch<R>>,
+    /// The approximate number of retired pointers in the list.
+    len: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct Batch<R> {
+    retired: Vec<R>,
+    next: *mut Batch<R>,
+}
+
+impl<R> SharedRetirees<R> {
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates an empty list.
+    pub const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates an empty list.
+    pub fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the approximate number of retired pointers in the list.
+    pub fn count(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Pushes a batch of retired pointers.
+    pub fn push(&self, retired: Vec<R>) {
+        let _ = self.len.fetch_add(retired.len(), Ordering::Relaxed);
+        let batch = Box::into_raw(Box::new(Batch {
+            retired,
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*batch).next = head };
+            match self
+                .head
+                .compare_exchange(head, batch, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    /// Takes all the retired pointers and appends them to `retired`.
+    pub fn adopt(&self, retired: &mut Vec<R>) {
+        if self.head.load(Ordering::Relaxed).is_null() {
+            return;
+        }
+        let mut batch = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        let mut adopted = 0;
+        while !batch.is_null() {
+            let mut b = unsafe { Box::from_raw(batch) };
+            adopted += b.retired.len();
+            retired.append(&mut b.retired);
+            batch = b.next;
+        }
+        let _ = self.len.fetch_sub(adopted, Ordering::Relaxed);
+    }
+}
+
+impl<R> Drop for SharedRetirees<R> {
+    fn drop(&mut self) {
+        let mut retired = Vec::new();
+        self.adopt(&mut retired);
+    }
+}
+
+/// Thread-local list of retired pointers of a domain.
 pub struct Retirees<'s> {
-    hazards: &'s Hazards,
-    /// The first element of the pair is the machine representation of a pointer without tag and
-    /// the second is the function pointer to `free::<T>` where `T` is the type of the object.
-    inner: Vec<(usize, unsafe fn(usize))>,
+    domain: &'s Domain,
+    inner: Vec<Retired>,
+    /// The record of the current thread, held from the first `retire` to report the pending count.
+    local: Option<&'s Node>,
 }
 
 impl<'s> Retirees<'s> {
-    /// The max length of retired pointer list. Call `collect` if the length becomes larger than
-    /// this value.
-    const THRESHOLD: usize = 64;
+    /// The min length of retired pointer list that triggers `collect`.
+    pub const MIN_THRESHOLD: usize = 64;
 
-    pub fn new(hazards: &'s Hazards) -> Self {
+    /// The ratio of the threshold to the number of hazard pointer slots.
+    ///
+    /// If the threshold is `R = H·k` where `H` is the number of slots, at most `H` pointers survive
+    /// a scan and at least `H·(k - 1)` pointers are freed. As a scan takes `O(H + R)` time, each
+    /// retire takes amortized `O(1)` time for `k > 1`.
+    const HAZARD_FACTOR: usize = 2;
+
+    pub fn new(domain: &'s Domain) -> Self {
         Self {
-            hazards,
+            domain,
             inner: Vec::new(),
+            local: None,
         }
     }
 
-    /// Retire a pointer.
-    pub fn retire<T>(&mut self, pointer: Shared<T>) {
-        unsafe fn free<T>(data: usize) {
-            debug_assert_eq!(align::decompose_tag::<T>(data).1, 0);
-            drop(Box::from_raw(data as *mut T))
-        }
-        self.inner.push((pointer.with_tag(0).into_usize(), free::<T>));
-        if self.inner.len() > Self::THRESHOLD {
+    #[cfg(test)]
+    /// Returns the number of the retired pointers that are not freed yet.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Retire a pointer, which will be reclaimed by `deleter`.
+    pub fn retire<T, D: Retire<T>>(&mut self, pointer: Shared<T>, deleter: D) {
+        self.inner.push(Retired::new(pointer, deleter));
+        self.domain.counters.retire();
+        if self.inner.len() > self.domain.threshold.load(Ordering::Relaxed) {
             self.collect();
+        } else {
+            self.update_pending();
         }
     }
 
-    /// Free the pointers that are `retire`d by the current thread and not `protect`ed by any other
-    /// threads.
+    /// Free the pointers that are `retire`d by the current thread or left in the domain's shared
+    /// list, and not `protect`ed by any other threads.
     pub fn collect(&mut self) {
-        fence(Ordering::SeqCst);
+        let start = Instant::now();
+        self.domain.retired.adopt(&mut self.inner);
+        membarrier::heavy();
+        let hazards = self.domain.hazards.all_hazards();
         let mut index = 0;
+        let mut freed = 0;
         while index != self.inner.len() {
-            let (pointer, free) = self.inner[index];
-            if self.hazards.all_hazards().contains(&pointer) {
+            if hazards.contains(&self.inner[index].pointer) {
                 index += 1;
             } else {
-                self.inner.remove(index);
-                unsafe { free(pointer) };
+                unsafe { self.inner.swap_remove(index).reclaim() };
+                freed += 1;
             }
         }
+        self.domain.counters.scan(freed, start.elapsed());
+        self.update_pending();
+
+        let threshold = Self::threshold(&self.domain.hazards);
+        self.domain.threshold.store(threshold, Ordering::Relaxed);
+    }
+
+    /// Returns the number of retired pointers that triggers a scan of `hazards`, adapted to the
+    /// number of the slots.
+    pub fn threshold(hazards: &Hazards) -> usize {
+        cmp::max(
+            Self::MIN_THRESHOLD,
+            Self::HAZARD_FACTOR * hazards.capacity(),
+        )
+    }
+
+    /// Hands over the remaining retired pointers to the domain's shared list.
+    pub fn flush(&mut self) {
+        if !self.inner.is_empty() {
+            self.domain.retired.push(mem::take(&mut self.inner));
+        }
+        self.update_pending();
+    }
+
+    /// Reports the number of the retired pointers buffered by the current thread.
+    fn update_pending(&mut self) {
+        let domain = self.domain;
+        let local = match self.local {
+            Some(local) => local,
+            // Only the threads that retire pointers are reported.
+            None if self.inner.is_empty() => return,
+            None => *self.local.get_or_insert(domain.hazards.hold(thread_key())),
+        };
+        local.set_pending(self.inner.len());
     }
 }
 
@@ -61,12 +295,42 @@ impl<'s> Retirees<'s> {
 #[cfg(not(feature = "check-loom"))]
 impl Drop for Retirees<'_> {
     fn drop(&mut self) {
-        // In a production-quality implementation of hazard pointers, the remaining local retired
-        // pointers will be moved to a global list of retired pointers, which are then reclaimed by
-        // the other threads. For pedagogical purposes, here we simply wait for all retired pointers
-        // are no longer protected.
-        while !self.inner.is_empty() {
-            self.collect();
+        // The pointers that are still protected are handed over to the other threads, so that the
+        // exiting thread doesn't wait for the other threads to drop their shields.
+        self.collect();
+        self.flush();
+        if self.local.is_some() {
+            let _ = self.domain.hazards.unhold(thread_key());
         }
     }
 }
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::*;
+    use crate::hazard_pointer::Owned;
+
+    #[test]
+    fn drop_without_reclaim() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Deleter;
+
+        impl Retire<usize> for Deleter {
+            unsafe fn reclaim(self, _: *mut usize) {
+                panic!("the deleter should not run");
+            }
+        }
+
+        impl Drop for Deleter {
+            fn drop(&mut self) {
+                let _ = DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let shared = Owned::new(1).into_shared();
+        drop(Retired::new(shared, Deleter));
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 1);
+        drop(unsafe { shared.into_owned() });
+    }
+}
@@ -0,0 +1,324 @@
+//! Treiber's lock-free stack protected by hazard pointers.
+
+use core::mem::ManuallyDrop;
+use core::ptr;
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::Ordering;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::Ordering;
+
+use super::{get_protected, retire, Atomic, Owned, Shared};
+
+/// Treiber's lock-free stack.
+///
+/// Usable with any number of producers and consumers. Popped nodes are reclaimed by hazard
+/// pointers, so a stalled thread holds at most the nodes it is popping.
+///
+/// # Example
+///
+/// ```
+/// use cs492_concur_homework::hazard_pointer::Stack;
+///
+/// let stack = Stack::new();
+/// stack.push(1);
+/// stack.push(2);
+/// assert_eq!(stack.pop(), Some(2));
+/// assert_eq!(stack.pop(), Some(1));
+/// assert!(stack.is_empty());
+/// ```
+#[derive(Debug)]
+pub struct Stack<T> {
+    head: Atomic<Node<T>>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    data: ManuallyDrop<T>,
+    next: Atomic<Node<T>>,
+}
+
+unsafe impl<T: Send> Sync for Stack<T> {}
+unsafe impl<T: Send> Send for Stack<T> {}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self {
+            head: Atomic::null(),
+        }
+    }
+}
+
+impl<T: 'static> Stack<T> {
+    /// Creates a new, empty stack.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pushes a value on top of the stack.
+    pub fn push(&self, t: T) {
+        let n = Self::alloc_node(t, Shared::null());
+        self.push_chain(n, n);
+    }
+
+    /// Pushes all values of `iter` on top of the stack, the last one ending up on the top.
+    ///
+    /// The values are first linked into a private chain, which is then published with a single
+    /// CAS. Hence the values are pushed atomically: no other operation observes a prefix of them.
+    pub fn push_all<I: IntoIterator<Item = T>>(&self, iter: I) {
+        let mut iter = iter.into_iter();
+        let first = match iter.next() {
+            Some(first) => first,
+            None => return,
+        };
+
+        let bottom = Self::alloc_node(first, Shared::null());
+        let mut top = bottom;
+        for t in iter {
+            top = Self::alloc_node(t, top);
+        }
+        self.push_chain(top, bottom);
+    }
+
+    fn alloc_node(t: T, next: Shared<Node<T>>) -> Shared<Node<T>> {
+        Owned::new(Node {
+            data: ManuallyDrop::new(t),
+            next: Atomic::from(next),
+        })
+        .into_shared()
+    }
+
+    /// Publishes the private chain from `top` to `bottom` on top of the stack.
+    fn push_chain(&self, top: Shared<Node<T>>, bottom: Shared<Node<T>>) {
+        // Pushing doesn't dereference shared nodes, so it needs no protection.
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe { bottom.deref() }
+                .next
+                .store(head, Ordering::Relaxed);
+
+            match self
+                .head
+                .compare_and_set(head, top, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    /// Attempts to pop the top element from the stack.
+    ///
+    /// Returns `None` if the stack is empty.
+    pub fn pop(&self) -> Option<T> {
+        loop {
+            let head = get_protected(&self.head).unwrap();
+            let h = unsafe { head.as_ref()? };
+            let next = h.next.load(Ordering::Relaxed);
+
+            if self
+                .head
+                .compare_and_set(head.shared(), next, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                // The node is still protected by `head` after it is retired.
+                retire(head.shared());
+                return Some(unsafe { ManuallyDrop::into_inner(ptr::read(&h.data)) });
+            }
+        }
+    }
+
+    /// Pops all elements from the stack at once.
+    ///
+    /// The stack is emptied with a single swap of its head, and the returned iterator yields the
+    /// popped elements from top to bottom.
+    pub fn pop_all(&self) -> PopAll<T> {
+        PopAll {
+            head: self.head.swap(Shared::null(), Ordering::Acquire),
+        }
+    }
+
+    /// Returns `true` if the stack is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+
+    /// Returns an iterator that pops elements until the stack is observed to be empty.
+    pub fn drain(&self) -> Drain<'_, T> {
+        Drain { stack: self }
+    }
+}
+
+/// A draining iterator of a [`Stack`], created by [`Stack::drain`].
+#[derive(Debug)]
+pub struct Drain<'s, T> {
+    stack: &'s Stack<T>,
+}
+
+impl<T: 'static> Iterator for Drain<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.stack.pop()
+    }
+}
+
+/// An owning iterator of a [`Stack`], which yields the elements from top to bottom.
+#[derive(Debug)]
+pub struct IntoIter<T> {
+    stack: Stack<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // The stack is owned, so the nodes are freed right away. The rest is freed by `Stack::drop`.
+        let head = self.stack.head.load(Ordering::Relaxed);
+        if head.is_null() {
+            return None;
+        }
+        let mut node = unsafe { head.into_owned() };
+        self.stack
+            .head
+            .store(node.next.load(Ordering::Relaxed), Ordering::Relaxed);
+        Some(unsafe { ManuallyDrop::take(&mut node.data) })
+    }
+}
+
+impl<T> IntoIterator for Stack<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { stack: self }
+    }
+}
+
+/// An owning iterator over the elements taken from a [`Stack`] by [`Stack::pop_all`].
+#[derive(Debug)]
+pub struct PopAll<T> {
+    /// The remaining chain. It is unreachable from the stack, but the nodes may still be read by
+    /// the concurrent `pop`s that protected them before the swap, so they are retired.
+    head: Shared<Node<T>>,
+}
+
+unsafe impl<T: Send> Send for PopAll<T> {}
+
+impl<T> Iterator for PopAll<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let head = self.head;
+        if head.is_null() {
+            return None;
+        }
+        let h = unsafe { head.deref() };
+        self.head = h.next.load(Ordering::Relaxed);
+        let data = unsafe { ManuallyDrop::into_inner(ptr::read(&h.data)) };
+        retire(head);
+        Some(data)
+    }
+}
+
+impl<T> Drop for PopAll<T> {
+    fn drop(&mut self) {
+        for _ in self {}
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        // The stack is owned, so the nodes are freed right away.
+        let mut node = self.head.load(Ordering::Relaxed);
+        while !node.is_null() {
+            let mut n = unsafe { node.into_owned() };
+            node = n.next.load(Ordering::Relaxed);
+            unsafe { ManuallyDrop::drop(&mut n.data) };
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::super::get_protected;
+    use super::Stack;
+    use crossbeam_utils::thread::scope;
+
+    #[test]
+    fn push_pop() {
+        let stack = Stack::new();
+        assert!(stack.is_empty());
+        for i in 0..200 {
+            stack.push(i);
+        }
+        assert!(!stack.is_empty());
+        for i in (0..200).rev() {
+            assert_eq!(stack.pop(), Some(i));
+        }
+        assert!(stack.pop().is_none());
+    }
+
+    #[test]
+    fn push_pop_concurrent() {
+        const THREADS: usize = 8;
+        const ITER: usize = 1024 * 8;
+
+        let stack = Stack::new();
+        scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|_| {
+                    for i in 0..ITER {
+                        stack.push(i);
+                        assert!(stack.pop().is_some());
+                    }
+                });
+            }
+        })
+        .unwrap();
+        assert!(stack.pop().is_none());
+    }
+
+    #[test]
+    fn push_all_pop_all() {
+        let stack = Stack::new();
+        stack.push(0);
+        stack.push_all(1..4);
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop_all().collect::<Vec<_>>(), [2, 1, 0]);
+        assert!(stack.is_empty());
+
+        stack.push_all(0..4);
+        assert_eq!(stack.drain().take(2).collect::<Vec<_>>(), [3, 2]);
+        assert_eq!(stack.into_iter().collect::<Vec<_>>(), [1, 0]);
+    }
+
+    #[test]
+    fn drop_values() {
+        let stack = Stack::new();
+        for i in 0..100 {
+            stack.push(Box::new(i));
+        }
+        assert_eq!(stack.pop().map(|b| *b), Some(99));
+        // The remaining boxes are freed by `Stack::drop`.
+    }
+
+    #[test]
+    fn stalled_reader_bounded() {
+        let stack = Stack::new();
+        stack.push(0);
+        super::super::stalled_reader_bounded(
+            // The reader stalls while protecting the top node.
+            || get_protected(&stack.head).unwrap(),
+            // The node is not freed even though it was popped long ago.
+            |shield| assert_eq!(unsafe { *shield.deref().data }, 0),
+            |i| {
+                if i == 0 {
+                    assert_eq!(stack.pop(), Some(0));
+                }
+                stack.push(i);
+                assert_eq!(stack.pop(), Some(i));
+            },
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Statistics of reclamation.
+
+use core::time::Duration;
+use std::fmt;
+
+// The statistics don't synchronize anything, so they don't need to be modeled by loom.
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use super::domain::Domain;
+
+/// Counters of the reclamation events of a domain.
+#[derive(Debug)]
+pub struct Counters {
+    retired: AtomicUsize,
+    freed: AtomicUsize,
+    scans: AtomicUsize,
+    scan_nanos: AtomicU64,
+}
+
+impl Counters {
+    /// Creates zeroed counters.
+    pub const fn new() -> Self {
+        Self {
+            retired: AtomicUsize::new(0),
+            freed: AtomicUsize::new(0),
+            scans: AtomicUsize::new(0),
+            scan_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Records a retired pointer.
+    pub fn retire(&self) {
+        let _ = self.retired.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a scan that took `time` and freed `freed` pointers.
+    pub fn scan(&self, freed: usize, time: Duration) {
+        let _ = self.freed.fetch_add(freed, Ordering::Relaxed);
+        let _ = self.scans.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .scan_nanos
+            .fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// A snapshot of the reclamation statistics of a domain.
+///
+/// The counters are read one by one while the other threads may be running, so they are only
+/// approximately consistent with each other.
+///
+/// The `Display` implementation dumps the statistics with the pointers protected by each thread.
+///
+/// # Example
+///
+/// ```
+/// use cs492_concur_homework::hazard_pointer::{get_protected, retire, stats, Atomic};
+///
+/// let atomic = Atomic::new(1);
+/// let shield = get_protected(&atomic).unwrap();
+/// retire(shield.shared());
+///
+/// let stats = stats();
+/// assert!(stats.retired >= 1);
+/// assert!(stats.protected >= 1);
+/// println!("{}", stats);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    /// The number of pointers retired to the domain.
+    pub retired: usize,
+    /// The number of retired pointers that are freed.
+    pub freed: usize,
+    /// The number of retired pointers in the list shared by the threads, i.e., the ones left by
+    /// exited threads or retired without thread-local buffering.
+    pub shared_pending: usize,
+    /// The number of scans of the hazard pointers.
+    pub scans: usize,
+    /// The total time spent in scans.
+    pub scan_time: Duration,
+    /// The number of pointers currently protected by shields.
+    pub protected: usize,
+    /// The statistics of each thread registered to the domain, sorted by the thread keys.
+    pub threads: Vec<ThreadStats>,
+}
+
+/// The reclamation statistics of a thread.
+#[derive(Debug, Clone, Default)]
+pub struct ThreadStats {
+    /// The key of the thread, as returned by `thread_key`.
+    pub key: usize,
+    /// The number of the retired pointers buffered by the thread.
+    pub pending: usize,
+    /// The addresses protected by the thread's shields.
+    pub hazards: Vec<usize>,
+}
+
+impl Stats {
+    /// Takes a snapshot of the statistics of `domain`.
+    pub(super) fn new(domain: &Domain) -> Self {
+        let counters = &domain.counters;
+        let mut threads = domain
+            .hazards
+            .threads()
+            .into_iter()
+            .map(|(key, pending, hazards)| ThreadStats {
+                key,
+                pending,
+                hazards,
+            })
+            .collect::<Vec<_>>();
+        threads.sort_by_key(|t| t.key);
+
+        Self {
+            retired: counters.retired.load(Ordering::Relaxed),
+            freed: counters.freed.load(Ordering::Relaxed),
+            shared_pending: domain.retired.count(),
+            scans: counters.scans.load(Ordering::Relaxed),
+            scan_time: Duration::from_nanos(counters.scan_nanos.load(Ordering::Relaxed)),
+            protected: threads.iter().map(|t| t.hazards.len()).sum(),
+            threads,
+        }
+    }
+
+    /// Returns the number of retired pointers that are not freed yet.
+    pub fn pending(&self) -> usize {
+        self.retired.saturating_sub(self.freed)
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "retired {}, freed {}, pending {} (shared {}), scans {} ({:?}), protected {}",
+            self.retired,
+            self.freed,
+            self.pending(),
+            self.shared_pending,
+            self.scans,
+            self.scan_time,
+            self.protected,
+        )?;
+        for thread in &self.threads {
+            write!(f, "thread {}: pending {}", thread.key, thread.pending)?;
+            if !thread.hazards.is_empty() {
+                write!(f, ", protecting")?;
+                for hazard in &thread.hazards {
+                    write!(f, " {:#x}", hazard)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -11,13 +11,14 @@ mod art;
 mod bst;
 mod elim_stack;
 mod hash_table;
+pub mod hazard_era;
 pub mod hazard_pointer;
 pub mod hello_server;
 mod linked_list;
 mod list_set;
 mod map;
 
-pub use arc::Arc;
+pub use arc::{Arc, AtomicArc, BiasedArc, Weak};
 pub use art::{Art, Entry};
 pub use bst::Bst;
 pub use elim_stack::ElimStack;
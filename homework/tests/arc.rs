@@ -18,7 +18,7 @@ impl Drop for Canary {
 
 #[cfg(not(feature = "check-loom"))]
 mod basic {
-    use cs492_concur_homework::Arc;
+    use cs492_concur_homework::{Arc, Weak};
 
     use super::mock::sync::atomic::{AtomicUsize, Ordering::Relaxed};
     use super::mock::sync::mpsc::channel;
@@ -144,6 +144,522 @@ mod basic {
         }
         assert_eq!(count.load(Relaxed), 8 * 128);
     }
+
+    #[test]
+    fn test_weak_count() {
+        let a = Arc::new(0);
+        assert!(Arc::count(&a) == 1);
+        assert!(Arc::weak_count(&a) == 0);
+        let w = Arc::downgrade(&a);
+        assert!(Arc::count(&a) == 1);
+        assert!(Arc::weak_count(&a) == 1);
+        let x = w.clone();
+        assert!(Arc::weak_count(&a) == 2);
+        drop(w);
+        drop(x);
+        assert!(Arc::count(&a) == 1);
+        assert!(Arc::weak_count(&a) == 0);
+        let c = a.clone();
+        assert!(Arc::count(&a) == 2);
+        assert!(Arc::weak_count(&a) == 0);
+        let d = Arc::downgrade(&c);
+        assert!(Arc::weak_count(&c) == 1);
+        assert!(Arc::count(&c) == 2);
+        drop(a);
+        drop(c);
+        drop(d);
+    }
+
+    #[test]
+    fn test_upgrade_downgrade() {
+        let x = Arc::new(5);
+        let y = Arc::downgrade(&x);
+        assert!(Arc::ptr_eq(&x, &y.upgrade().unwrap()));
+        drop(x);
+        assert!(y.upgrade().is_none());
+
+        let empty: Weak<usize> = Weak::new();
+        assert!(empty.upgrade().is_none());
+        assert!(empty.clone().upgrade().is_none());
+    }
+
+    #[test]
+    fn test_weak_drop_data_once() {
+        let canary = AtomicUsize::new(0);
+        let x = Arc::new(Canary(&canary as *const AtomicUsize));
+        let y = Arc::downgrade(&x);
+        drop(x);
+        // The data is dropped even though there is a `Weak`.
+        assert!(canary.load(Relaxed) == 1);
+        assert!(y.upgrade().is_none());
+        drop(y);
+        assert!(canary.load(Relaxed) == 1);
+    }
+
+    #[test]
+    fn test_get_mut_with_weak() {
+        let mut x = Arc::new(3);
+        let y = Arc::downgrade(&x);
+        assert!(Arc::get_mut(&mut x).is_none());
+        drop(y);
+        *Arc::get_mut(&mut x).unwrap() = 4;
+        assert_eq!(*x, 4);
+        let _z = Arc::downgrade(&x);
+        assert!(Arc::get_mut(&mut x).is_none());
+    }
+
+    #[test]
+    fn test_try_unwrap_with_weak() {
+        let x = Arc::new(3);
+        let y = Arc::downgrade(&x);
+        assert_eq!(Arc::try_unwrap(x).unwrap(), 3);
+        assert!(y.upgrade().is_none());
+
+        let x = Arc::new(4);
+        let z = Arc::downgrade(&x);
+        let w = z.upgrade().unwrap();
+        let x = Arc::try_unwrap(x).unwrap_err();
+        drop(w);
+        assert_eq!(Arc::try_unwrap(x).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_cowarc_make_mut_weak() {
+        let mut cow0 = Arc::new(75);
+        let cow1_weak = Arc::downgrade(&cow0);
+
+        assert!(75 == *cow0);
+        assert!(75 == *cow1_weak.upgrade().unwrap());
+
+        *Arc::make_mut(&mut cow0) += 1;
+
+        assert!(76 == *cow0);
+        assert!(cow1_weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_weak_stress() {
+        let arc = Arc::new(AtomicUsize::new(0));
+        let weak = Arc::downgrade(&arc);
+        let handles = (0..8)
+            .map(|_| {
+                let weak = weak.clone();
+                thread::spawn(move || {
+                    for _ in 0..128 {
+                        let arc = weak.upgrade().unwrap();
+                        arc.fetch_add(1, Relaxed);
+                        drop(Arc::downgrade(&arc));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(arc.load(Relaxed), 8 * 128);
+        assert_eq!(Arc::weak_count(&arc), 1);
+        drop(arc);
+        assert!(weak.upgrade().is_none());
+    }
+}
+
+#[cfg(not(feature = "check-loom"))]
+mod unsized_data {
+    use cs492_concur_homework::{Arc, Weak};
+    use std::fmt::Display;
+
+    use super::mock::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+    use super::Canary;
+
+    trait Named {
+        fn name(&self) -> String;
+    }
+
+    impl Named for usize {
+        fn name(&self) -> String {
+            format!("usize {}", self)
+        }
+    }
+
+    impl Named for Canary {
+        fn name(&self) -> String {
+            "canary".to_string()
+        }
+    }
+
+    #[test]
+    fn slice() {
+        let v: Arc<[usize]> = Arc::from(vec![1, 2, 3]);
+        let w = v.clone();
+        assert_eq!(&w[..], [1, 2, 3]);
+        assert!(Arc::ptr_eq(&v, &w));
+
+        let empty: Arc<[usize]> = Arc::from(Vec::new());
+        assert!(empty.is_empty());
+        let zst: Arc<[()]> = Arc::from(vec![(); 3]);
+        assert_eq!(zst.len(), 3);
+    }
+
+    #[test]
+    fn slice_drop_once() {
+        let canary = AtomicUsize::new(0);
+        let v = (0..5)
+            .map(|_| Canary(&canary as *const AtomicUsize))
+            .collect::<Vec<_>>();
+        let x: Arc<[Canary]> = Arc::from(v);
+        let y = x.clone();
+        assert_eq!(canary.load(Relaxed), 0);
+        drop(x);
+        assert_eq!(canary.load(Relaxed), 0);
+        drop(y);
+        assert_eq!(canary.load(Relaxed), 5);
+    }
+
+    #[test]
+    fn str() {
+        let s: Arc<str> = Arc::from("foo");
+        assert_eq!(&*s, "foo");
+        let t: Arc<str> = Arc::from(String::from("bar"));
+        assert_eq!(format!("{}{}", s, t), "foobar");
+        let empty: Arc<str> = Arc::from("");
+        assert_eq!(&*empty, "");
+    }
+
+    #[test]
+    fn trait_object() {
+        let canary = AtomicUsize::new(0);
+        let boxed: Box<dyn Named + Send + Sync> = Box::new(Canary(&canary as *const AtomicUsize));
+        let x: Arc<dyn Named + Send + Sync> = Arc::from(boxed);
+        let y = x.clone();
+        assert_eq!(y.name(), "canary");
+        drop(x);
+        drop(y);
+        assert_eq!(canary.load(Relaxed), 1);
+
+        let five: Arc<dyn Named> = Arc::from(Box::new(5usize) as Box<dyn Named>);
+        assert_eq!(five.name(), "usize 5");
+        let display: Arc<dyn Display> = Arc::from(Box::new("display") as Box<dyn Display>);
+        assert_eq!(display.to_string(), "display");
+    }
+
+    #[test]
+    fn unsized_weak() {
+        let mut x: Arc<[usize]> = Arc::from(vec![1, 2, 3]);
+        let y = Arc::downgrade(&x);
+        assert!(Arc::get_mut(&mut x).is_none());
+        assert_eq!(&y.upgrade().unwrap()[..], [1, 2, 3]);
+        drop(x);
+        assert!(y.upgrade().is_none());
+
+        let mut x: Arc<str> = Arc::from("foo");
+        Arc::get_mut(&mut x).unwrap().make_ascii_uppercase();
+        assert_eq!(&*x, "FOO");
+    }
+
+    #[test]
+    fn new_cyclic() {
+        struct Node {
+            me: Weak<Node>,
+            value: usize,
+        }
+
+        let node = Arc::new_cyclic(|me| {
+            assert!(me.upgrade().is_none());
+            Node {
+                me: me.clone(),
+                value: 3,
+            }
+        });
+        assert_eq!(node.me.upgrade().unwrap().value, 3);
+        assert_eq!(Arc::count(&node), 1);
+        assert_eq!(Arc::weak_count(&node), 1);
+
+        let me = node.me.clone();
+        drop(node);
+        assert!(me.upgrade().is_none());
+    }
+
+    #[test]
+    fn new_cyclic_panic() {
+        let weak = std::sync::Mutex::new(None);
+        let result = std::panic::catch_unwind(|| {
+            Arc::<usize>::new_cyclic(|me| {
+                *weak.lock().unwrap() = Some(me.clone());
+                panic!()
+            })
+        });
+        assert!(result.is_err());
+        let weak: Weak<usize> = weak.into_inner().unwrap().unwrap();
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn pin() {
+        let x = Arc::pin(5);
+        let y = x.clone();
+        assert_eq!(*x, 5);
+        assert_eq!(*y, 5);
+    }
+}
+
+#[cfg(not(feature = "check-loom"))]
+mod atomic {
+    use crossbeam_utils::thread::scope;
+    use cs492_concur_homework::hazard_pointer::collect;
+    use cs492_concur_homework::{Arc, AtomicArc};
+    use std::thread;
+
+    use super::mock::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+    use super::Canary;
+
+    #[test]
+    fn load_store() {
+        let cell = AtomicArc::new(Arc::new(1));
+        let one = cell.load();
+        assert_eq!(*one, 1);
+        assert_eq!(Arc::count(&one), 2);
+
+        cell.store(Arc::new(2));
+        assert_eq!(*cell.load(), 2);
+        assert_eq!(*one, 1);
+
+        let two = cell.swap(Arc::new(3));
+        assert_eq!(*two, 2);
+        assert_eq!(Arc::try_unwrap(cell.into_inner()).unwrap(), 3);
+    }
+
+    #[test]
+    fn compare_and_swap() {
+        let first = Arc::new(1);
+        let cell = AtomicArc::new(first.clone());
+        let second = Arc::new(2);
+        assert!(cell.compare_and_swap(&first, second.clone()).is_ok());
+        assert!(Arc::ptr_eq(&cell.load(), &second));
+
+        // Comparison is by allocation, not by value.
+        let err = cell
+            .compare_and_swap(&Arc::new(2), Arc::new(3))
+            .unwrap_err();
+        assert_eq!(*err, 3);
+        assert!(Arc::ptr_eq(&cell.load(), &second));
+    }
+
+    #[test]
+    fn drop_once() {
+        const THREADS: usize = 4;
+        const ITER: usize = 1024;
+
+        let canary = AtomicUsize::new(0);
+        let new = || Arc::new(Canary(&canary as *const AtomicUsize));
+        let cell = AtomicArc::new(new());
+        scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|_| {
+                    for _ in 0..ITER {
+                        let current = cell.load();
+                        drop(cell.swap(new()));
+                        let _ = cell.compare_and_swap(&current, new());
+                        cell.store(new());
+                    }
+                });
+            }
+        })
+        .unwrap();
+        drop(cell);
+        // Free the replaced values left by the exited threads. The other tests may adopt some of
+        // them in the meantime, which are freed by their later scans.
+        while canary.load(Relaxed) != 1 + THREADS * ITER * 3 {
+            collect();
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn stress() {
+        const THREADS: usize = 4;
+        const ITER: usize = 1024 * 4;
+
+        let cell = AtomicArc::new(Arc::new(0));
+        scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|_| {
+                    for _ in 0..ITER {
+                        loop {
+                            let current = cell.load();
+                            if cell
+                                .compare_and_swap(&current, Arc::new(*current + 1))
+                                .is_ok()
+                            {
+                                break;
+                            }
+                        }
+                    }
+                });
+            }
+            s.spawn(|_| {
+                let mut last = 0;
+                for _ in 0..ITER {
+                    let value = *cell.load();
+                    assert!(value >= last);
+                    last = value;
+                }
+            });
+        })
+        .unwrap();
+        assert_eq!(*cell.load(), THREADS * ITER);
+    }
+}
+
+#[cfg(not(feature = "check-loom"))]
+mod biased {
+    use crossbeam_utils::thread::scope;
+    use cs492_concur_homework::BiasedArc;
+    use std::sync::mpsc::channel;
+
+    use super::mock::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+    use super::mock::thread;
+    use super::Canary;
+
+    #[test]
+    fn owner_clone_drop() {
+        let canary = AtomicUsize::new(0);
+        let x = BiasedArc::new(Canary(&canary as *const AtomicUsize));
+        let y = x.clone();
+        assert_eq!(BiasedArc::count(&x), 2);
+        assert!(BiasedArc::ptr_eq(&x, &y));
+        drop(x);
+        assert_eq!(BiasedArc::count(&y), 1);
+        drop(y);
+        assert_eq!(canary.load(Relaxed), 1);
+    }
+
+    #[test]
+    fn get_mut_try_unwrap() {
+        let mut x = BiasedArc::new(3);
+        *BiasedArc::get_mut(&mut x).unwrap() = 4;
+        let y = x.clone();
+        assert!(BiasedArc::get_mut(&mut x).is_none());
+        let x = BiasedArc::try_unwrap(x).unwrap_err();
+        drop(y);
+        assert_eq!(BiasedArc::try_unwrap(x).unwrap(), 4);
+
+        let mut cow0 = BiasedArc::new(75);
+        let mut cow1 = cow0.clone();
+        *BiasedArc::make_mut(&mut cow0) += 1;
+        *BiasedArc::make_mut(&mut cow1) += 2;
+        assert_eq!(*cow0, 76);
+        assert_eq!(*cow1, 77);
+        assert!(!BiasedArc::ptr_eq(&cow0, &cow1));
+    }
+
+    #[test]
+    /// The last `BiasedArc` is dropped by another thread after the owner's implicit merge.
+    fn implicit_merge() {
+        let canary = AtomicUsize::new(0);
+        let x = BiasedArc::new(Canary(&canary as *const AtomicUsize));
+        // Another thread clones `x`, which updates the shared counter.
+        let y = scope(|s| s.spawn(|_| x.clone()).join().unwrap()).unwrap();
+        assert_eq!(BiasedArc::count(&x), 2);
+
+        // The biased counter reaches zero, so the counters are merged.
+        drop(x);
+        assert_eq!(canary.load(Relaxed), 0);
+        thread::spawn(move || drop(y)).join().unwrap();
+        assert_eq!(canary.load(Relaxed), 1);
+    }
+
+    #[test]
+    /// Another thread makes the shared counter negative, and the owner merges it explicitly.
+    fn explicit_merge() {
+        let canary = AtomicUsize::new(0);
+        let mut x = BiasedArc::new(Canary(&canary as *const AtomicUsize));
+        let y = x.clone();
+        thread::spawn(move || drop(y)).join().unwrap();
+        assert_eq!(BiasedArc::count(&x), 1);
+
+        // The allocation is queued, and `get_mut` merges it.
+        assert!(BiasedArc::get_mut(&mut x).is_some());
+        let z = x.clone();
+        thread::spawn(move || drop(z)).join().unwrap();
+        drop(x);
+        assert_eq!(canary.load(Relaxed), 1);
+
+        // The queued allocation is freed on the owner's next `new`.
+        let x = BiasedArc::new(Canary(&canary as *const AtomicUsize));
+        let y = x.clone();
+        drop(x);
+        thread::spawn(move || drop(y)).join().unwrap();
+        assert_eq!(canary.load(Relaxed), 1);
+        drop(BiasedArc::new(()));
+        assert_eq!(canary.load(Relaxed), 2);
+    }
+
+    #[test]
+    /// The owner exits before the other threads drop the `BiasedArc`s.
+    fn owner_exit() {
+        let canary = AtomicUsize::new(0);
+        let canary_ptr = &canary as *const AtomicUsize as usize;
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            let x = BiasedArc::new(Canary(canary_ptr as *const AtomicUsize));
+            for _ in 0..4 {
+                tx.send(x.clone()).unwrap();
+            }
+        })
+        .join()
+        .unwrap();
+
+        let handles = rx
+            .iter()
+            .map(|x: BiasedArc<Canary>| {
+                thread::spawn(move || {
+                    for _ in 0..128 {
+                        drop(x.clone());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(canary.load(Relaxed), 1);
+    }
+
+    #[test]
+    fn stress() {
+        const THREADS: usize = 8;
+        const ITER: usize = 1024;
+
+        let canary = AtomicUsize::new(0);
+        let x = BiasedArc::new(Canary(&canary as *const AtomicUsize));
+        let count = BiasedArc::new(AtomicUsize::new(0));
+        scope(|s| {
+            for _ in 0..THREADS {
+                let x = x.clone();
+                let count = count.clone();
+                s.spawn(move |_| {
+                    let mut local = Vec::new();
+                    for i in 0..ITER {
+                        local.push(x.clone());
+                        if i % 2 == 0 {
+                            drop(local.pop());
+                        }
+                        count.fetch_add(1, Relaxed);
+                    }
+                });
+            }
+            for _ in 0..ITER {
+                drop(x.clone());
+            }
+        })
+        .unwrap();
+        assert_eq!(count.load(Relaxed), THREADS * ITER);
+        assert_eq!(canary.load(Relaxed), 0);
+        drop(x);
+        // The shared counter may be negative, and the allocation is freed on the explicit merge.
+        drop(BiasedArc::new(()));
+        assert_eq!(canary.load(Relaxed), 1);
+    }
 }
 
 mod correctness {
@@ -151,7 +667,7 @@ mod correctness {
     use super::mock::sync::atomic::{AtomicUsize, Ordering::Relaxed};
     use super::mock::thread;
     use super::Canary;
-    use cs492_concur_homework::Arc;
+    use cs492_concur_homework::{Arc, BiasedArc};
 
     #[test]
     /// data:=123 → flag.count:=1 → flag.count==1 → data==123
@@ -245,4 +761,60 @@ mod correctness {
             assert_eq!(canary.load(Relaxed), 1);
         })
     }
+
+    #[test]
+    /// value:=123 → upgraded arc drop → weak drop → get_mut success
+    fn get_mut_weak_sync() {
+        model(|| {
+            let mut value = Arc::new(AtomicUsize::new(0));
+            {
+                let weak = Arc::downgrade(&value);
+                thread::spawn(move || {
+                    if let Some(value) = weak.upgrade() {
+                        value.store(123, Relaxed);
+                    }
+                });
+            }
+            if let Some(val) = Arc::get_mut(&mut value) {
+                assert_eq!(val.load(Relaxed), 123);
+            }
+        })
+    }
+
+    #[test]
+    /// Resistence against arbitrary interleaving of `upgrade` and the last `drop`.
+    fn upgrade_drop_atomic() {
+        model(|| {
+            let canary = AtomicUsize::new(0);
+            let arc = Arc::new(Canary(&canary as *const AtomicUsize));
+            let weak = Arc::downgrade(&arc);
+            let handle = thread::spawn(move || {
+                drop(weak.upgrade());
+                drop(weak);
+            });
+            drop(arc);
+            handle.join().unwrap();
+            assert_eq!(canary.load(Relaxed), 1);
+        })
+    }
+
+    #[test]
+    /// Resistence against arbitrary interleaving of the owner's and the other's `drop`.
+    fn biased_clone_drop_atomic() {
+        model(|| {
+            let canary = AtomicUsize::new(0);
+            let arc1 = BiasedArc::new(Canary(&canary as *const AtomicUsize));
+            let arc2 = arc1.clone();
+            let handle = thread::spawn(move || {
+                drop(arc1.clone());
+                drop(arc1);
+            });
+            drop(arc2.clone());
+            drop(arc2);
+            handle.join().unwrap();
+            // The allocation may be queued if the other thread dropped first.
+            drop(BiasedArc::new(()));
+            assert_eq!(canary.load(Relaxed), 1);
+        })
+    }
 }
@@ -0,0 +1,127 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering::*};
+
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::hazard_era::{collect, get_protected, retire, Atomic, Owned, Shared};
+
+/// Counts the dropped objects.
+struct Canary(&'static AtomicUsize);
+
+impl Drop for Canary {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Relaxed);
+    }
+}
+
+#[test]
+fn birth_retire_eras() {
+    static OLD: AtomicUsize = AtomicUsize::new(0);
+    static NEW: AtomicUsize = AtomicUsize::new(0);
+
+    let old = Atomic::new(Canary(&OLD));
+    let atomic = Atomic::new(0usize);
+    // Reserves an era in which `old` is alive.
+    let shield = get_protected(&atomic).unwrap();
+    // Advances the era.
+    collect();
+
+    // `new` is born after the reserved era, so it is freed although the shield is held.
+    let new = Owned::new(Canary(&NEW)).into_shared();
+    retire(new);
+    // `old` is not protected by the shield itself, but it is alive in the reserved era.
+    let shared = old.load(Relaxed);
+    old.store(Shared::null(), Relaxed);
+    retire(shared);
+    collect();
+    assert_eq!(NEW.load(Relaxed), 1);
+    assert_eq!(OLD.load(Relaxed), 0);
+
+    drop(shield);
+    while OLD.load(Relaxed) == 0 {
+        collect();
+    }
+    retire(atomic.load(Relaxed));
+}
+
+#[test]
+fn reprotect_in_same_era() {
+    const READERS: usize = 4;
+    const ITER: usize = 1024 * 16;
+    const MAGIC: usize = 0x5eed;
+
+    let atomic = Atomic::new(MAGIC);
+    let done = AtomicBool::new(false);
+    scope(|s| {
+        for _ in 0..READERS {
+            s.spawn(|_| {
+                // The shield is reused, so it protects the next pointer without a fence while the
+                // era stays the same.
+                let mut shield = get_protected(&atomic).unwrap();
+                while !done.load(Acquire) {
+                    let _ = shield.protect(&atomic);
+                    assert_eq!(unsafe { *shield.deref() }, MAGIC);
+                }
+            });
+        }
+        s.spawn(|_| {
+            for i in 0..ITER {
+                let old = atomic.load(Acquire);
+                atomic.store(Owned::new(MAGIC).into_shared(), Release);
+                retire(old);
+                if i % 64 == 0 {
+                    collect();
+                }
+            }
+            done.store(true, Release);
+        });
+    })
+    .unwrap();
+    retire(atomic.load(Relaxed));
+}
+
+#[test]
+fn stalled_reader_bounded() {
+    const ITER: usize = 1024 * 16;
+    static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+    struct Canary(&'static AtomicUsize);
+
+    impl Canary {
+        fn new() -> Self {
+            LIVE.fetch_add(1, Relaxed);
+            Self(&LIVE)
+        }
+    }
+
+    impl Drop for Canary {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Relaxed);
+        }
+    }
+
+    let atomic = Atomic::new(Canary::new());
+    // A reader stalls while holding a shield.
+    let mut shield = get_protected(&atomic).unwrap();
+    scope(|s| {
+        s.spawn(|_| {
+            for _ in 0..ITER {
+                let old = atomic.load(Acquire);
+                atomic.store(Owned::new(Canary::new()).into_shared(), Release);
+                retire(old);
+            }
+            collect();
+        });
+    })
+    .unwrap();
+
+    // Only the objects alive in the reserved era are kept.
+    assert!(LIVE.load(Relaxed) < 1024);
+
+    // The shield protects the current object in the current era.
+    let cur = shield.protect(&atomic);
+    assert_eq!(cur.into_usize(), atomic.load(Relaxed).into_usize());
+    drop(shield);
+    retire(atomic.load(Relaxed));
+    while LIVE.load(Relaxed) != 0 {
+        collect();
+    }
+}
@@ -1,12 +1,13 @@
 use core::mem::ManuallyDrop;
 use core::ptr;
-use core::sync::atomic::Ordering::*;
-use std::thread::sleep;
+use core::sync::atomic::{AtomicUsize, Ordering::*};
+use std::thread::{self, sleep};
 use std::time::Duration;
 
 use crossbeam_utils::thread::scope;
 use cs492_concur_homework::hazard_pointer::{
-    collect, get_protected, protect, retire, Atomic, Owned,
+    collect, collect_in, get_protected, get_protected_in, protect, retire, retire_in, retire_with,
+    shield_set, stats, thread_key, Atomic, Domain, HazardPointer, Owned, Retire, Shared,
 };
 
 #[test]
@@ -131,6 +132,79 @@ fn counter_tag() {
     retire(cur);
 }
 
+#[test]
+fn counter_compare_exchange() {
+    const THREADS: usize = 4;
+    const ITER: usize = 1024 * 16;
+
+    let count = Atomic::new(0usize);
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for _ in 0..ITER {
+                    let mut new = Owned::new(0);
+                    loop {
+                        let cur_shield = get_protected(&count).unwrap();
+                        *new = unsafe { *cur_shield.deref() } + 1;
+                        match count.compare_exchange_weak(cur_shield.shared(), new, AcqRel, Acquire)
+                        {
+                            Ok(_) => {
+                                retire(cur_shield.shared());
+                                break;
+                            }
+                            // The new pointer is handed back on failure.
+                            Err(e) => new = e.new,
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert_eq!(unsafe { *count.load(Acquire).deref() }, THREADS * ITER);
+    drop(unsafe { count.into_owned() });
+}
+
+#[test]
+fn atomic_operations() {
+    let atomic = Atomic::<u64>::from(Owned::new(1));
+    let one = atomic.load(Relaxed);
+
+    // swap
+    let two = atomic.swap(Owned::new(2), Relaxed);
+    assert_eq!(two.into_usize(), one.into_usize());
+    let two = atomic.load(Relaxed);
+    assert_eq!(unsafe { *two.deref() }, 2);
+
+    // compare_exchange
+    let err = atomic
+        .compare_exchange(one, Owned::new(3), Relaxed, Relaxed)
+        .unwrap_err();
+    assert_eq!(err.current.into_usize(), two.into_usize());
+    assert_eq!(*err.new, 3);
+    let three = atomic
+        .compare_exchange(two, err.new, Relaxed, Relaxed)
+        .unwrap();
+    assert_eq!(atomic.load(Relaxed).into_usize(), three.into_usize());
+    assert!(atomic
+        .compare_exchange(three, three.with_tag(1), Relaxed, Relaxed)
+        .is_ok());
+
+    // tags
+    assert_eq!(atomic.fetch_or(2, Relaxed).tag(), 1);
+    assert_eq!(atomic.fetch_xor(1, Relaxed).tag(), 3);
+    assert_eq!(atomic.fetch_and(4, Relaxed).tag(), 2);
+    assert_eq!(atomic.load(Relaxed).tag(), 0);
+    assert_eq!(atomic.load(Relaxed).into_usize(), three.into_usize());
+
+    unsafe {
+        drop(one.into_owned());
+        drop(two.into_owned());
+        assert_eq!(*atomic.into_owned(), 3);
+        assert!(Atomic::<u64>::default().try_into_owned().is_none());
+    }
+}
+
 #[test]
 fn stack() {
     const THREADS: usize = 8;
@@ -174,6 +248,374 @@ fn two_stacks() {
     assert!(stack1.pop().is_none());
 }
 
+#[test]
+fn lockfree_stack() {
+    const THREADS: usize = 8;
+    const ITER: usize = 1024 * 8;
+
+    let stack = lockfree::Stack::<usize, HazardPointer>::default();
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for i in 0..ITER {
+                    stack.push(i);
+                    assert!(stack.pop().is_some());
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(stack.pop().is_none());
+}
+
+#[test]
+fn lockfree_queue() {
+    const THREADS: usize = 8;
+    const ITER: usize = 1024 * 8;
+
+    let queue = lockfree::Queue::<usize, HazardPointer>::default();
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for i in 0..ITER {
+                    queue.push(i, &());
+                    assert!(queue.try_pop(&()).is_some());
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(queue.try_pop(&()).is_none());
+}
+
+#[test]
+fn lockfree_list() {
+    const THREADS: usize = 8;
+    const ITER: usize = 1024 * 8;
+
+    let list = lockfree::List::<usize, usize, HazardPointer>::default();
+    scope(|s| {
+        for t in 0..THREADS {
+            let list = &list;
+            s.spawn(move |_| {
+                for i in 0..ITER {
+                    // The keys of the threads are interleaved, so that the traversals pass through
+                    // the nodes that the other threads are deleting.
+                    let key = i % 16 * THREADS + t;
+                    assert!(list.harris_michael_insert(key, key, &()));
+                    assert_eq!(list.harris_michael_lookup_cloned(&key, &()), Some(key));
+                    assert_eq!(list.harris_michael_delete_cloned(&key, &()), Some(key));
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(list.harris_michael_lookup_cloned(&0, &()).is_none());
+}
+
+#[test]
+fn exit_while_protected() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    // Not zero-sized, so that the canary has a real allocation.
+    struct Canary(&'static AtomicUsize);
+
+    impl Drop for Canary {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    let atomic = Atomic::new(Canary(&DROPPED));
+    let shield = get_protected(&atomic).unwrap();
+    scope(|s| {
+        s.spawn(|_| {
+            let shared = atomic.load(Relaxed);
+            atomic.store(Shared::null(), Relaxed);
+            retire(shared);
+            // The thread exits without waiting for the shield to be dropped.
+        });
+    })
+    .unwrap();
+    assert_eq!(DROPPED.load(Relaxed), 0);
+
+    drop(shield);
+    while DROPPED.load(Relaxed) == 0 {
+        collect();
+        thread::yield_now();
+    }
+    assert_eq!(DROPPED.load(Relaxed), 1);
+}
+
+#[test]
+fn domain() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Canary(&'static AtomicUsize);
+
+    impl Drop for Canary {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    let domain = Domain::new();
+    let atomics = (0..3)
+        .map(|_| Atomic::new(Canary(&DROPPED)))
+        .collect::<Vec<_>>();
+    let shield = get_protected_in(&domain, &atomics[0]).unwrap();
+    // A shield of the default domain doesn't protect the pointers retired to the other domains.
+    let default_shield = get_protected(&atomics[1]).unwrap();
+    for atomic in &atomics {
+        let shared = atomic.load(Relaxed);
+        atomic.store(Shared::null(), Relaxed);
+        retire_in(&domain, shared);
+    }
+
+    collect();
+    assert_eq!(DROPPED.load(Relaxed), 0);
+    collect_in(&domain);
+    assert_eq!(DROPPED.load(Relaxed), 2);
+
+    drop(shield);
+    drop(default_shield);
+    collect_in(&domain);
+    assert_eq!(DROPPED.load(Relaxed), 3);
+
+    // The remaining retired pointers are freed with the domain.
+    let atomic = Atomic::new(Canary(&DROPPED));
+    let shared = atomic.load(Relaxed);
+    retire_in(&domain, shared);
+    drop(domain);
+    assert_eq!(DROPPED.load(Relaxed), 4);
+}
+
+#[test]
+fn domain_release_records() {
+    const THREADS: usize = 16;
+
+    let domain = Domain::new();
+    let atomic = Atomic::new(0usize);
+    for _ in 0..THREADS {
+        scope(|s| {
+            let _ = s.spawn(|_| {
+                let shield = get_protected_in(&domain, &atomic).unwrap();
+                assert_eq!(domain.stats().threads.len(), 1);
+                drop(shield);
+            });
+        })
+        .unwrap();
+    }
+    // The records of the exited threads are released once their last shields are dropped.
+    assert!(domain.stats().threads.is_empty());
+    drop(unsafe { atomic.into_owned() });
+}
+
+#[test]
+fn retire_with_deleter() {
+    static RECLAIMED: AtomicUsize = AtomicUsize::new(0);
+
+    /// Frees the object and counts the reclaimed objects.
+    struct CountingDeleter(&'static AtomicUsize);
+
+    impl<T> Retire<T> for CountingDeleter {
+        unsafe fn reclaim(self, pointer: *mut T) {
+            drop(Box::from_raw(pointer));
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    let atomics = (0..10).map(Atomic::new).collect::<Vec<_>>();
+    let shield = get_protected(&atomics[0]).unwrap();
+    for atomic in &atomics {
+        let shared = atomic.load(Relaxed);
+        atomic.store(Shared::null(), Relaxed);
+        retire_with(shared, CountingDeleter(&RECLAIMED));
+    }
+    collect();
+    assert_eq!(RECLAIMED.load(Relaxed), 9);
+
+    drop(shield);
+    collect();
+    assert_eq!(RECLAIMED.load(Relaxed), 10);
+}
+
+#[test]
+fn stats_domain() {
+    let domain = Domain::new();
+    let atomics = (0..3).map(Atomic::new).collect::<Vec<_>>();
+    let shield = get_protected_in(&domain, &atomics[0]).unwrap();
+    for atomic in &atomics {
+        let shared = atomic.load(Relaxed);
+        atomic.store(Shared::null(), Relaxed);
+        retire_in(&domain, shared);
+    }
+    collect_in(&domain);
+
+    let stats = domain.stats();
+    assert_eq!(stats.retired, 3);
+    assert_eq!(stats.freed, 2);
+    assert_eq!(stats.pending(), 1);
+    assert_eq!(stats.shared_pending, 1);
+    assert_eq!(stats.scans, 1);
+    assert_eq!(stats.protected, 1);
+    // The dump shows which thread pins the protected pointer.
+    let thread = stats
+        .threads
+        .iter()
+        .find(|t| t.key == thread_key())
+        .unwrap();
+    assert_eq!(thread.hazards, vec![shield.shared().into_usize()]);
+    let line = format!(
+        "thread {}: pending 0, protecting {:#x}",
+        thread_key(),
+        shield.shared().into_usize()
+    );
+    assert!(stats.to_string().contains(&line));
+
+    drop(shield);
+    collect_in(&domain);
+    let stats = domain.stats();
+    assert_eq!(stats.freed, 3);
+    assert_eq!(stats.scans, 2);
+    assert_eq!(stats.protected, 0);
+}
+
+#[test]
+fn stats_pending() {
+    let atomic = Atomic::new(0);
+    scope(|s| {
+        s.spawn(|_| {
+            let shield = get_protected(&atomic).unwrap();
+            atomic.store(Shared::null(), Relaxed);
+            retire(shield.shared());
+            collect();
+
+            // The retired pointer is buffered by the current thread while it is protected.
+            let snapshot = stats();
+            let thread = snapshot
+                .threads
+                .iter()
+                .find(|t| t.key == thread_key())
+                .unwrap();
+            assert_eq!(thread.pending, 1);
+            assert_eq!(thread.hazards, vec![shield.shared().into_usize()]);
+
+            drop(shield);
+            collect();
+            let snapshot = stats();
+            let thread = snapshot
+                .threads
+                .iter()
+                .find(|t| t.key == thread_key())
+                .unwrap();
+            assert_eq!(thread.pending, 0);
+            assert!(thread.hazards.is_empty());
+        });
+    })
+    .unwrap();
+}
+
+#[test]
+fn protect_from_swap() {
+    struct Node {
+        value: usize,
+        next: Atomic<Node>,
+    }
+
+    let head = Atomic::null();
+    for value in 0..100 {
+        let node = Owned::new(Node {
+            value,
+            next: Atomic::null(),
+        })
+        .into_shared();
+        unsafe { node.deref() }
+            .next
+            .store(head.load(Relaxed), Relaxed);
+        head.store(node, Relaxed);
+    }
+
+    scope(|s| {
+        s.spawn(|_| {
+            // Hand-over-hand traversal with two slots.
+            let mut curr = get_protected(&head).unwrap();
+            let mut next = get_protected(&head).unwrap();
+            let mut sum = 0;
+            while let Some(node) = unsafe { curr.as_ref() } {
+                sum += node.value;
+                let _ = next.protect_from(&node.next);
+                curr.swap(&mut next);
+                let snapshot = stats();
+                let thread = snapshot
+                    .threads
+                    .iter()
+                    .find(|t| t.key == thread_key())
+                    .unwrap();
+                // The null pointer past the last node is not reported.
+                let expected = if curr.is_null() { 1 } else { 2 };
+                assert_eq!(thread.hazards.len(), expected);
+            }
+            assert_eq!(sum, (0..100).sum());
+        });
+    })
+    .unwrap();
+
+    let mut node = head.load(Relaxed);
+    while !node.is_null() {
+        let owned = unsafe { node.into_owned() };
+        node = owned.next.load(Relaxed);
+    }
+}
+
+#[test]
+fn shield_set_protect() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Canary(&'static AtomicUsize);
+
+    impl Drop for Canary {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    let atomics = (0..3)
+        .map(|_| Atomic::new(Canary(&DROPPED)))
+        .collect::<Vec<_>>();
+    let mut shields = shield_set(3);
+    assert_eq!(shields.len(), 3);
+    assert!(shields.iter().all(|s| s.is_null()));
+    shields.protect_from(&atomics.iter().collect::<Vec<_>>());
+    for (shield, atomic) in shields.iter().zip(&atomics) {
+        assert!(shield.validate(atomic.load(Relaxed)));
+    }
+
+    for atomic in &atomics {
+        let shared = atomic.load(Relaxed);
+        atomic.store(Shared::null(), Relaxed);
+        retire(shared);
+    }
+    collect();
+    assert_eq!(DROPPED.load(Relaxed), 0);
+
+    // Move on with the last pointer only.
+    shields.swap(0, 2);
+    shields.protect_from(&[&Atomic::null(), &Atomic::null()]);
+    collect();
+    assert_eq!(DROPPED.load(Relaxed), 2);
+
+    drop(shields);
+    collect();
+    assert_eq!(DROPPED.load(Relaxed), 3);
+}
+
+#[test]
+#[should_panic(expected = "empty shield set")]
+fn shield_set_empty() {
+    let _ = shield_set::<usize>(0);
+}
+
 /// Treiber's lock-free stack.
 ///
 /// Usable with any number of producers and consumers.
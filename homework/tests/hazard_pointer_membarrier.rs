@@ -0,0 +1,48 @@
+//! Hazard pointers with asymmetric fences. The fences are fixed for the whole process on the first
+//! use, so this test is in its own binary.
+
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::hazard_pointer::{enable_asymmetric_fences, Queue, Stack};
+
+#[cfg(target_os = "linux")]
+/// Returns whether the kernel supports `MEMBARRIER_CMD_PRIVATE_EXPEDITED`. See `membarrier(2)`.
+fn membarrier_supported() -> bool {
+    const MEMBARRIER_CMD_QUERY: libc::c_int = 0;
+    const MEMBARRIER_CMD_PRIVATE_EXPEDITED: libc::c_int = 1 << 3;
+
+    let supported = unsafe { libc::syscall(libc::SYS_membarrier, MEMBARRIER_CMD_QUERY, 0) };
+    supported >= 0 && supported & libc::c_long::from(MEMBARRIER_CMD_PRIVATE_EXPEDITED) != 0
+}
+
+#[test]
+fn stack_queue() {
+    const THREADS: usize = 4;
+    const ITER: usize = 1024 * 8;
+
+    let enabled = enable_asymmetric_fences();
+    #[cfg(target_os = "linux")]
+    assert_eq!(enabled, membarrier_supported());
+    #[cfg(not(target_os = "linux"))]
+    assert!(!enabled);
+
+    let stack = Stack::new();
+    let queue = Queue::new();
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for i in 0..ITER {
+                    stack.push(i);
+                    queue.push(i);
+                    assert!(stack.pop().is_some());
+                    assert!(queue.try_pop().is_some());
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(stack.is_empty());
+    assert!(queue.is_empty());
+
+    // The fences are fixed once used.
+    assert_eq!(enable_asymmetric_fences(), enabled);
+}
@@ -0,0 +1,114 @@
+//! Compares `Stack` and `Queue` with and without node pools.
+//!
+//! Each thread repeatedly pushes a value and then pops one. The number of heap allocations is
+//! counted by a global allocator. Run with `cargo bench --bench pool`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crossbeam_epoch::pin;
+use crossbeam_utils::thread::scope;
+use lockfree::{Queue, Stack};
+
+const OPS_PER_THREAD: usize = 200_000;
+const THREADS: &[usize] = &[1, 4];
+const POOL_CAP: usize = 256;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Runs `op` on `threads` threads, and returns the elapsed time and the number of allocations.
+fn run<F: Fn(usize) + Sync>(threads: usize, op: F) -> (Duration, usize) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|_| {
+                for i in 0..OPS_PER_THREAD {
+                    op(i);
+                }
+            });
+        }
+    })
+    .unwrap();
+    (
+        start.elapsed(),
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+    )
+}
+
+fn report(name: &str, threads: usize, (elapsed, allocations): (Duration, usize)) {
+    println!(
+        "{:>16} {:>8} {:>16.2} {:>12}",
+        name,
+        threads,
+        (2 * threads * OPS_PER_THREAD) as f64 / elapsed.as_secs_f64() / 1e6,
+        allocations
+    );
+}
+
+fn main() {
+    println!(
+        "{:>16} {:>8} {:>16} {:>12}",
+        "", "threads", "Mop/s", "allocations"
+    );
+    for &threads in THREADS {
+        let stack = Stack::new();
+        report(
+            "Stack",
+            threads,
+            run(threads, |i| {
+                stack.push(i);
+                let _ = stack.pop();
+            }),
+        );
+
+        let stack = Stack::with_pool(POOL_CAP);
+        report(
+            "Stack (pool)",
+            threads,
+            run(threads, |i| {
+                stack.push(i);
+                let _ = stack.pop();
+            }),
+        );
+
+        let queue = Queue::new();
+        report(
+            "Queue",
+            threads,
+            run(threads, |i| {
+                let guard = pin();
+                queue.push(i, &guard);
+                let _ = queue.try_pop(&guard);
+            }),
+        );
+
+        let queue = Queue::with_pool(POOL_CAP);
+        report(
+            "Queue (pool)",
+            threads,
+            run(threads, |i| {
+                let guard = pin();
+                queue.push(i, &guard);
+                let _ = queue.try_pop(&guard);
+            }),
+        );
+    }
+}
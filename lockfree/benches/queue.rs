@@ -0,0 +1,77 @@
+//! Compares the scalability of `Queue` and `SegQueue` under contention.
+//!
+//! Each thread repeatedly pushes a value and then pops one, so that all threads contend on both
+//! ends of the queue. Run with `cargo bench --bench queue`.
+
+use std::time::{Duration, Instant};
+
+use crossbeam_epoch::{pin, Guard};
+use crossbeam_utils::thread::scope;
+use lockfree::{Queue, SegQueue};
+
+const OPS_PER_THREAD: usize = 200_000;
+const THREADS: &[usize] = &[1, 2, 4, 8, 16];
+
+trait ConcurrentQueue<T>: Default + Sync {
+    fn push(&self, t: T, guard: &Guard);
+    fn try_pop(&self, guard: &Guard) -> Option<T>;
+}
+
+impl<T: Send> ConcurrentQueue<T> for Queue<T> {
+    fn push(&self, t: T, guard: &Guard) {
+        Queue::push(self, t, guard)
+    }
+
+    fn try_pop(&self, guard: &Guard) -> Option<T> {
+        Queue::try_pop(self, guard)
+    }
+}
+
+impl<T: Send> ConcurrentQueue<T> for SegQueue<T> {
+    fn push(&self, t: T, guard: &Guard) {
+        SegQueue::push(self, t, guard)
+    }
+
+    fn try_pop(&self, guard: &Guard) -> Option<T> {
+        SegQueue::try_pop(self, guard)
+    }
+}
+
+fn run<Q: ConcurrentQueue<usize>>(threads: usize) -> Duration {
+    let queue = Q::default();
+    let start = Instant::now();
+    scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|_| {
+                for i in 0..OPS_PER_THREAD {
+                    let guard = pin();
+                    queue.push(i, &guard);
+                    let _ = queue.try_pop(&guard);
+                }
+            });
+        }
+    })
+    .unwrap();
+    start.elapsed()
+}
+
+fn mops(threads: usize, elapsed: Duration) -> f64 {
+    (2 * threads * OPS_PER_THREAD) as f64 / elapsed.as_secs_f64() / 1e6
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>16} {:>16}",
+        "threads", "Queue (Mop/s)", "SegQueue (Mop/s)"
+    );
+    for &threads in THREADS {
+        let ms = run::<Queue<usize>>(threads);
+        let seg = run::<SegQueue<usize>>(threads);
+        println!(
+            "{:>8} {:>16.2} {:>16.2}",
+            threads,
+            mops(threads, ms),
+            mops(threads, seg)
+        );
+    }
+}
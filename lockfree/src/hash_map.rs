@@ -0,0 +1,486 @@
+//! Lock-free resizable hash map.
+//!
+//! Shalev and Shavit.  Split-Ordered Lists: Lock-Free Extensible Hash Tables.  J. ACM 2006.
+//! https://dl.acm.org/doi/10.1145/1147954.1147958
+
+use core::cmp;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+
+use crossbeam_epoch::{Guard, Owned};
+
+use crate::list::{self, Cursor, List, Node};
+
+/// The number of segments of the bucket array. Segment `i` contains the buckets in
+/// `[2^(i-1), 2^i)`, and segment 0 contains bucket 0.
+const SEGMENTS: usize = 32;
+
+/// The maximum number of buckets.
+const MAX_BUCKETS: usize = 1 << (SEGMENTS - 1);
+
+/// Key of the underlying list.
+///
+/// The list is sorted by the recursive split order `so`. Bucket sentinels have even split orders
+/// and no key, and entries have odd split orders. Note that `Ord` only compares the split orders:
+/// entries with the same split order are told apart with `Cursor::find_harris_michael_by`.
+#[derive(Debug)]
+struct SoKey<K> {
+    so: usize,
+    key: Option<K>,
+}
+
+impl<K> PartialEq for SoKey<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.so == other.so
+    }
+}
+
+impl<K> Eq for SoKey<K> {}
+
+impl<K> PartialOrd for SoKey<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K> Ord for SoKey<K> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.so.cmp(&other.so)
+    }
+}
+
+type ListNode<K, V> = Node<SoKey<K>, Option<V>>;
+
+/// Lock-free hash map based on split-ordered list.
+///
+/// All entries are stored in a single `List` sorted by the bit-reversed hashes, and each bucket is
+/// a pointer to a sentinel node in the list. Doubling the number of buckets does not move any
+/// entry: the new buckets are lazily initialized by inserting their sentinels in the middle of
+/// the existing buckets.
+#[derive(Debug)]
+pub struct HashMap<K, V, S = RandomState> {
+    list: List<SoKey<K>, Option<V>>,
+    /// Segments of the pointers to the bucket sentinels, lazily allocated.
+    segments: [AtomicPtr<AtomicPtr<ListNode<K, V>>>; SEGMENTS],
+    /// The number of buckets, which is a power of two.
+    size: AtomicUsize,
+    /// The number of entries.
+    count: AtomicUsize,
+    hash_builder: S,
+}
+
+impl<K, V, S> Default for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> Drop for HashMap<K, V, S> {
+    fn drop(&mut self) {
+        // The sentinels are owned by `list`, so only the segments are freed here.
+        for (i, segment) in self.segments.iter().enumerate() {
+            let segment = segment.load(Ordering::Relaxed);
+            if !segment.is_null() {
+                let len = Self::segment_len(i);
+                drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(segment, len)) });
+            }
+        }
+    }
+}
+
+impl<K, V> HashMap<K, V>
+where
+    K: Hash + Eq,
+{
+    /// Creates a new, empty hash map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    /// `size` is doubled when `count > size * LOAD_FACTOR`.
+    const LOAD_FACTOR: usize = 2;
+
+    fn segment_len(segment: usize) -> usize {
+        if segment == 0 {
+            1
+        } else {
+            1 << (segment - 1)
+        }
+    }
+
+    /// Returns the segment containing the given bucket and the offset of the bucket in the
+    /// segment. The offset is the index without its most significant bit.
+    fn locate(index: usize) -> (usize, usize) {
+        let segment = (0usize.leading_zeros() - index.leading_zeros()) as usize;
+        if segment == 0 {
+            (0, 0)
+        } else {
+            (segment, index ^ (1 << (segment - 1)))
+        }
+    }
+
+    /// Returns the number of entries.
+    ///
+    /// The result may be stale if there are concurrent insertions or deletions.
+    pub fn len(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the map has no entries.
+    ///
+    /// The result may be stale if there are concurrent insertions or deletions.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the entries, in an unspecified order.
+    ///
+    /// The entries concurrently inserted or deleted may or may not be visited.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, K, V> {
+        Iter {
+            inner: self.list.iter(guard),
+        }
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Creates a new, empty hash map which will use the given hash builder to hash keys.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            list: List::new(),
+            segments: Default::default(),
+            size: AtomicUsize::new(2),
+            count: AtomicUsize::new(0),
+            hash_builder,
+        }
+    }
+
+    fn hash(&self, key: &K) -> usize {
+        let mut hasher = self.hash_builder.build_hasher();
+        key.hash(&mut hasher);
+        hasher.finish() as usize
+    }
+
+    /// Returns the slot for the pointer to the sentinel of the given bucket, allocating its
+    /// segment if necessary.
+    fn bucket_slot(&self, index: usize) -> &AtomicPtr<ListNode<K, V>> {
+        let (segment, offset) = Self::locate(index);
+
+        let mut ptr = self.segments[segment].load(Ordering::Acquire);
+        if ptr.is_null() {
+            let new = (0..Self::segment_len(segment))
+                .map(|_| AtomicPtr::new(ptr::null_mut()))
+                .collect::<Box<[AtomicPtr<ListNode<K, V>>]>>();
+            let new = Box::into_raw(new) as *mut AtomicPtr<ListNode<K, V>>;
+            match self.segments[segment].compare_exchange(
+                ptr::null_mut(),
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => ptr = new,
+                Err(current) => {
+                    let len = Self::segment_len(segment);
+                    drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(new, len)) });
+                    ptr = current;
+                }
+            }
+        }
+
+        unsafe { &*ptr.add(offset) }
+    }
+
+    /// Creates a cursor at the sentinel of the given bucket. If the bucket doesn't exist,
+    /// recursively initializes the buckets.
+    fn bucket<'g>(&'g self, index: usize, guard: &'g Guard) -> Cursor<'g, SoKey<K>, Option<V>> {
+        let slot = self.bucket_slot(index);
+        let sentinel = slot.load(Ordering::Acquire);
+        if !sentinel.is_null() {
+            // Sentinels are never deleted.
+            return unsafe { Cursor::from_ptr(slot, sentinel) };
+        }
+
+        let key = SoKey {
+            so: index.reverse_bits(),
+            key: None,
+        };
+        let mut node = Owned::new(Node::new(key, None));
+        let cursor = loop {
+            // The parent bucket is `index` without its most significant bit. The search restarts
+            // from a fresh cursor, since the head of the list may have changed.
+            let mut cursor = if index == 0 {
+                self.list.head(guard)
+            } else {
+                let (_, parent) = Self::locate(index);
+                self.bucket(parent, guard)
+            };
+            match cursor.find_harris_michael(node.key(), guard) {
+                Ok(true) => break cursor,
+                Ok(false) => (),
+                Err(()) => continue,
+            }
+            match cursor.insert(node, guard) {
+                Ok(()) => break cursor,
+                Err(n) => node = n,
+            }
+        };
+
+        // All threads that initialize this bucket find the same sentinel.
+        slot.store(cursor.curr().as_raw() as *mut _, Ordering::Release);
+        cursor
+    }
+
+    /// Finds `key`, and returns whether it's found and a cursor at the key or at the position to
+    /// insert the key. Also returns the number of buckets at the start of the search.
+    fn find<'g>(
+        &'g self,
+        key: &K,
+        guard: &'g Guard,
+    ) -> (usize, bool, Cursor<'g, SoKey<K>, Option<V>>) {
+        let hash = self.hash(key);
+        let so = hash.reverse_bits() | 1;
+        let size = self.size.load(Ordering::Acquire);
+        let bucket = self.bucket(hash & (size - 1), guard);
+
+        // Entries with the same split order but a different key are treated as smaller, so that
+        // the search continues past them.
+        let cmp = |k: &SoKey<K>| match k.so.cmp(&so) {
+            cmp::Ordering::Equal if k.key.as_ref() != Some(key) => cmp::Ordering::Less,
+            ord => ord,
+        };
+        loop {
+            let mut cursor = bucket.clone();
+            if let Ok(found) = cursor.find_harris_michael_by(cmp, guard) {
+                return (size, found, cursor);
+            }
+        }
+    }
+
+    /// Lookups the value for the given key.
+    pub fn lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        let (_, found, cursor) = self.find(key, guard);
+        if found {
+            cursor.lookup().and_then(Option::as_ref)
+        } else {
+            None
+        }
+    }
+
+    /// Inserts a key-value pair. Returns the pair back if the key already exists.
+    pub fn insert(&self, key: K, value: V, guard: &Guard) -> Result<(), (K, V)> {
+        let so = self.hash(&key).reverse_bits() | 1;
+        let mut node = Owned::new(Node::new(SoKey { so, key: Some(key) }, Some(value)));
+
+        let size = loop {
+            let (size, found, mut cursor) = self.find(node.key().key.as_ref().unwrap(), guard);
+            if found {
+                let (key, value) = node.into_box().into_inner();
+                return Err((key.key.unwrap(), value.unwrap()));
+            }
+
+            match cursor.insert(node, guard) {
+                Ok(()) => break size,
+                Err(n) => node = n,
+            }
+        };
+
+        let count = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+        if count > size * Self::LOAD_FACTOR && size < MAX_BUCKETS {
+            let _ =
+                self.size
+                    .compare_exchange(size, size * 2, Ordering::Release, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Deletes the given key and returns its value.
+    pub fn delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        loop {
+            let (_, found, cursor) = self.find(key, guard);
+            if !found {
+                return None;
+            }
+
+            if let Ok(value) = cursor.delete(guard) {
+                self.count.fetch_sub(1, Ordering::Relaxed);
+                return value.as_ref();
+            }
+        }
+    }
+}
+
+/// Iterator over the entries of a [`HashMap`], created by [`HashMap::iter`].
+#[derive(Debug)]
+pub struct Iter<'g, K, V> {
+    inner: list::Iter<'g, SoKey<K>, Option<V>>,
+}
+
+impl<'g, K, V> Iterator for Iter<'g, K, V> {
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, value) = self.inner.next()?;
+            if let (Some(key), Some(value)) = (key.key.as_ref(), value.as_ref()) {
+                return Some((key, value));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_epoch::pin;
+    use crossbeam_utils::thread::scope;
+    use std::collections::HashSet;
+    use std::hash::BuildHasherDefault;
+    use std::sync::Barrier;
+
+    #[test]
+    fn insert_lookup_delete_seq() {
+        let map = HashMap::new();
+        let guard = &pin();
+        for i in 0..1000 {
+            assert_eq!(map.insert(i, i.to_string(), guard), Ok(()));
+        }
+        assert_eq!(map.len(), 1000);
+        assert_eq!(
+            map.insert(7, "x".to_string(), guard),
+            Err((7, "x".to_string()))
+        );
+        for i in 0..1000 {
+            assert_eq!(map.lookup(&i, guard), Some(&i.to_string()));
+        }
+        for i in (0..1000).step_by(2) {
+            assert_eq!(map.delete(&i, guard), Some(&i.to_string()));
+        }
+        assert_eq!(map.delete(&0, guard), None);
+        assert_eq!(map.len(), 500);
+        for i in 0..1000 {
+            assert_eq!(map.lookup(&i, guard).is_some(), i % 2 == 1);
+        }
+    }
+
+    /// Hashes every key to the same value.
+    #[derive(Debug, Default)]
+    struct ConstHasher;
+
+    impl Hasher for ConstHasher {
+        fn finish(&self) -> u64 {
+            42
+        }
+
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    #[test]
+    fn hash_collision() {
+        let map = HashMap::<_, _, BuildHasherDefault<ConstHasher>>::default();
+        let guard = &pin();
+        for i in 0..100 {
+            assert!(map.insert(i, i, guard).is_ok());
+        }
+        assert!(map.insert(50, 0, guard).is_err());
+        assert_eq!(map.delete(&50, guard), Some(&50));
+        for i in 0..100 {
+            assert_eq!(map.lookup(&i, guard).is_some(), i != 50);
+        }
+        assert_eq!(map.iter(guard).count(), 99);
+    }
+
+    #[test]
+    fn iter() {
+        let map = HashMap::new();
+        let guard = &pin();
+        for i in 0..100 {
+            map.insert(i, i * 2, guard).unwrap();
+        }
+        map.delete(&3, guard);
+        let entries = map
+            .iter(guard)
+            .map(|(&k, &v)| (k, v))
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            entries,
+            (0..100).filter(|&i| i != 3).map(|i| (i, i * 2)).collect()
+        );
+    }
+
+    #[test]
+    fn insert_delete_concurrent() {
+        const THREADS: usize = 8;
+        const COUNT: usize = 10_000;
+
+        let map = HashMap::new();
+        scope(|s| {
+            for t in 0..THREADS {
+                let map = &map;
+                s.spawn(move |_| {
+                    for i in 0..COUNT {
+                        let key = i * THREADS + t;
+                        assert!(map.insert(key, key, &pin()).is_ok());
+                    }
+                    for i in (0..COUNT).step_by(2) {
+                        let key = i * THREADS + t;
+                        assert_eq!(map.delete(&key, &pin()), Some(&key));
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        let guard = &pin();
+        assert_eq!(map.len(), THREADS * COUNT / 2);
+        for key in 0..THREADS * COUNT {
+            let expected = if (key / THREADS) % 2 == 1 {
+                Some(&key)
+            } else {
+                None
+            };
+            assert_eq!(map.lookup(&key, guard), expected);
+        }
+        assert_eq!(map.iter(guard).count(), THREADS * COUNT / 2);
+    }
+
+    #[test]
+    fn first_insert_concurrent() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 5_000;
+
+        // All threads initialize the buckets of an empty map at the same time.
+        for _ in 0..ROUNDS {
+            let map = HashMap::new();
+            let barrier = Barrier::new(THREADS);
+            scope(|s| {
+                for t in 0..THREADS {
+                    let map = &map;
+                    let barrier = &barrier;
+                    s.spawn(move |_| {
+                        barrier.wait();
+                        assert!(map.insert(t, t, &pin()).is_ok());
+                    });
+                }
+            })
+            .unwrap();
+
+            let guard = &pin();
+            assert_eq!(map.len(), THREADS);
+            for t in 0..THREADS {
+                assert_eq!(map.lookup(&t, guard), Some(&t));
+            }
+        }
+    }
+}
@@ -8,10 +8,22 @@ extern crate crossbeam_utils;
 
 #[macro_use]
 mod utils;
+mod hash_map;
 pub mod list;
-mod queue;
-mod stack;
+mod pool;
+mod priority_queue;
+pub mod queue;
+pub mod reclaim;
+mod seg_queue;
+pub mod spsc;
+pub mod stack;
+mod wait_free_queue;
 
+pub use hash_map::HashMap;
 pub use list::List;
+pub use priority_queue::PriorityQueue;
 pub use queue::Queue;
-pub use stack::Stack;
+pub use reclaim::{Epoch, Reclaim};
+pub use seg_queue::SegQueue;
+pub use stack::{PopAll, Stack};
+pub use wait_free_queue::WaitFreeQueue;
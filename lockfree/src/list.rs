@@ -1,60 +1,117 @@
 //! Lock-free singly linked list.
 
-use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Pointer, Shared};
-
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
 use std::cmp::Ordering::{Equal, Greater, Less};
-use std::sync::atomic::Ordering;
+
+use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
+
+use crate::reclaim::{Epoch, Reclaim};
 
 /// Linked list node.
 #[derive(Debug)]
 pub struct Node<K, V> {
-    /// Mark: tag(), Tag: not needed
-    next: Atomic<Node<K, V>>,
+    /// Mark: the least significant bit
+    next: AtomicPtr<Node<K, V>>,
     key: K,
     value: V,
 }
 
+/// Returns the mark of `ptr`.
+#[inline]
+fn tag<T>(ptr: *mut T) -> usize {
+    ptr as usize & 1
+}
+
+/// Returns `ptr` with the mark `tag`.
+#[inline]
+fn with_tag<T>(ptr: *mut T, tag: usize) -> *mut T {
+    (ptr as usize & !1 | tag) as *mut T
+}
+
 /// Sorted singly linked list.
+///
+/// Deleted nodes are reclaimed by the scheme `R`. The operations with the Harris-Michael
+/// traversal are available for all schemes, and the others only for [`Epoch`], since they access
+/// the nodes that are already unlinked. With the other schemes, a value can't be borrowed after
+/// the operation, so it's cloned instead, e.g., by [`List::harris_michael_lookup_cloned`].
 #[derive(Debug)]
-pub struct List<K, V> {
-    head: Atomic<Node<K, V>>,
+pub struct List<K, V, R: Reclaim = Epoch> {
+    head: AtomicPtr<Node<K, V>>,
+    _marker: PhantomData<R>,
 }
 
-impl<K, V> Default for List<K, V>
-where
-    K: Ord,
-{
+// The keys and the values are read by all threads, and dropped by any thread.
+unsafe impl<K: Send + Sync, V: Send + Sync, R: Reclaim> Sync for List<K, V, R> {}
+unsafe impl<K: Send + Sync, V: Send + Sync, R: Reclaim> Send for List<K, V, R> {}
+
+impl<K, V, R: Reclaim> Default for List<K, V, R> {
     fn default() -> Self {
-        Self::new()
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            _marker: PhantomData,
+        }
     }
 }
 
-impl<K, V> Drop for List<K, V> {
+impl<K, V, R: Reclaim> Drop for List<K, V, R> {
     fn drop(&mut self) {
-        unsafe {
-            let mut curr = self.head.load(Ordering::Relaxed, unprotected());
-            while !curr.is_null() {
-                let curr_ref = curr.deref_mut();
-                let next = curr_ref.next.load(Ordering::Relaxed, unprotected());
-                drop(curr.into_owned());
-                curr = next;
-            }
+        let mut curr = self.head.load(Ordering::Relaxed);
+        while !curr.is_null() {
+            let node = unsafe { Box::from_raw(with_tag(curr, 0)) };
+            curr = node.next.load(Ordering::Relaxed);
         }
     }
 }
 
 /// Linked list cursor.
+///
+/// The cursor protects the node that contains `prev` and the current node with its shields, so
+/// that they are not freed while the cursor is alive.
+#[derive(Debug)]
+pub struct Cursor<'g, K, V, R: Reclaim = Epoch> {
+    /// The head of the list, or the `next` of the node protected by `prev_shield`.
+    prev: &'g AtomicPtr<Node<K, V>>,
+    curr: *mut Node<K, V>,
+    prev_shield: R::Shield,
+    curr_shield: R::Shield,
+}
+
+/// Iterator over the entries of a list, created by [`List::iter`].
 #[derive(Debug)]
-pub struct Cursor<'g, K, V> {
-    prev: &'g Atomic<Node<K, V>>,
-    curr: Shared<'g, Node<K, V>>,
+pub struct Iter<'g, K, V> {
+    /// The nodes are not freed while the guard is alive.
+    curr: Option<&'g Node<K, V>>,
+    _guard: &'g Guard,
 }
 
-impl<'g, K, V> Clone for Cursor<'g, K, V> {
+impl<'g, K, V> Iterator for Iter<'g, K, V> {
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let curr_node = self.curr?;
+            let next = curr_node.next.load(Ordering::Acquire);
+            self.curr = unsafe { with_tag(next, 0).as_ref() };
+            if tag(next) == 0 {
+                return Some((&curr_node.key, &curr_node.value));
+            }
+        }
+    }
+}
+
+impl<'g, K, V, R: Reclaim> Clone for Cursor<'g, K, V, R>
+where
+    R::Shield: Clone,
+{
     fn clone(&self) -> Self {
         Self {
             prev: self.prev,
             curr: self.curr,
+            prev_shield: self.prev_shield.clone(),
+            curr_shield: self.curr_shield.clone(),
         }
     }
 }
@@ -63,7 +120,7 @@ impl<K, V> Node<K, V> {
     /// Creates a new node.
     pub fn new(key: K, value: V) -> Self {
         Self {
-            next: Atomic::null(),
+            next: AtomicPtr::new(ptr::null_mut()),
             key,
             value,
         }
@@ -73,27 +130,185 @@ impl<K, V> Node<K, V> {
     pub fn into_value(self) -> V {
         self.value
     }
+
+    /// Returns the key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Extracts the key and the value.
+    pub fn into_inner(self) -> (K, V) {
+        (self.key, self.value)
+    }
 }
 
-impl<'g, K, V> Cursor<'g, K, V>
+impl<'g, K, V, R> Cursor<'g, K, V, R>
 where
     K: Ord,
+    R: Reclaim,
 {
     /// Creates a cursor from raw pointers.
     ///
     /// # Safety
     ///
-    /// TODO
-    pub unsafe fn from_raw(prev: *const Atomic<Node<K, V>>, curr: *const Node<K, V>) -> Self {
+    /// `prev` should point to `curr`, and neither `curr` nor the node containing `prev` should be
+    /// freed while the cursor is alive, e.g., they are sentinels that are never deleted.
+    pub(crate) unsafe fn from_ptr(
+        prev: *const AtomicPtr<Node<K, V>>,
+        curr: *const Node<K, V>,
+    ) -> Self {
         Self {
             prev: &*prev,
-            curr: Shared::from_usize(curr as usize),
+            curr: curr as *mut _,
+            prev_shield: R::Shield::default(),
+            curr_shield: R::Shield::default(),
+        }
+    }
+
+    /// Protects the node that `prev` points to, and makes it the current node.
+    ///
+    /// Fails if the node containing `prev` is logically removed, because then the node it points
+    /// to may be already unlinked.
+    #[inline]
+    fn protect_curr(&mut self, guard: &'g R::Guard) -> Result<(), ()> {
+        let curr = R::protect(self.prev, &mut self.curr_shield, guard);
+        if tag(curr) != 0 {
+            return Err(());
+        }
+        self.curr = curr;
+        Ok(())
+    }
+
+    /// Clean up a single logically removed node in each traversal.
+    #[inline]
+    pub fn find_harris_michael(&mut self, key: &K, guard: &'g R::Guard) -> Result<bool, ()> {
+        self.find_harris_michael_by(|k| k.cmp(key), guard)
+    }
+
+    /// Like `find_harris_michael`, but compares the keys with `cmp`, which returns the ordering of
+    /// the given key with respect to the search key.
+    ///
+    /// `cmp` may order the keys differently from `Ord`, as long as the nodes it considers less
+    /// than the search key form a prefix of the list.
+    #[inline]
+    pub fn find_harris_michael_by<F>(&mut self, cmp: F, guard: &'g R::Guard) -> Result<bool, ()>
+    where
+        F: Fn(&K) -> std::cmp::Ordering,
+    {
+        loop {
+            debug_assert_eq!(tag(self.curr), 0);
+
+            let curr_node = some_or!(unsafe { self.curr.as_ref() }, return Ok(false));
+            let next = curr_node.next.load(Ordering::Acquire);
+
+            if tag(next) != 0 {
+                let _ = self
+                    .prev
+                    .compare_exchange(
+                        self.curr,
+                        with_tag(next, 0),
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                    )
+                    .map_err(|_| ())?;
+                unsafe { R::retire(self.curr, guard) };
+                self.protect_curr(guard)?;
+                continue;
+            }
+
+            match cmp(&curr_node.key) {
+                Less => {
+                    mem::swap(&mut self.prev_shield, &mut self.curr_shield);
+                    self.prev = &curr_node.next;
+                    self.protect_curr(guard)?;
+                }
+                Equal => return Ok(true),
+                Greater => return Ok(false),
+            }
+        }
+    }
+
+    /// Inserts a node before the current node, and makes it the current node. Returns the node
+    /// back if `prev` no longer points to the current node.
+    #[inline]
+    fn insert_node(
+        &mut self,
+        node: Box<Node<K, V>>,
+        guard: &'g R::Guard,
+    ) -> Result<(), Box<Node<K, V>>> {
+        node.next.store(self.curr, Ordering::Relaxed);
+        let node = Box::into_raw(node);
+
+        // The node is protected before it's published, so that it stays protected as the current
+        // node.
+        let mut shield = R::Shield::default();
+        let _ = R::protect(&AtomicPtr::new(node), &mut shield, guard);
+
+        match self
+            .prev
+            .compare_exchange(self.curr, node, Ordering::Release, Ordering::Relaxed)
+        {
+            Ok(_) => {
+                self.curr = node;
+                self.curr_shield = shield;
+                Ok(())
+            }
+            Err(_) => Err(unsafe { Box::from_raw(node) }),
         }
     }
 
+    /// Marks the current node as deleted, and tries to unlink it. Fails if the node is already
+    /// marked.
+    #[inline]
+    fn delete_node(&mut self, guard: &'g R::Guard) -> Result<(), ()> {
+        let curr_node = unsafe { self.curr.as_ref() }.unwrap();
+
+        let mut next = curr_node.next.load(Ordering::Relaxed);
+        loop {
+            if tag(next) == 1 {
+                return Err(());
+            }
+            match curr_node.next.compare_exchange(
+                next,
+                with_tag(next, 1),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(n) => next = n,
+            }
+        }
+
+        if self
+            .prev
+            .compare_exchange(self.curr, next, Ordering::Release, Ordering::Relaxed)
+            .is_ok()
+        {
+            unsafe { R::retire(self.curr, guard) };
+        }
+
+        Ok(())
+    }
+}
+
+impl<'g, K, V> Cursor<'g, K, V>
+where
+    K: Ord,
+{
+    /// Creates a cursor from raw pointers.
+    ///
+    /// # Safety
+    ///
+    /// `prev` should point to `curr`, and neither `curr` nor the node containing `prev` should be
+    /// freed while the cursor is alive, e.g., they are sentinels that are never deleted.
+    pub unsafe fn from_raw(prev: *const Atomic<Node<K, V>>, curr: *const Node<K, V>) -> Self {
+        // `Atomic` has the representation of a pointer.
+        Self::from_ptr(prev as *const AtomicPtr<_>, curr)
+    }
+
     /// Returns the current node.
     pub fn curr(&self) -> Shared<'g, Node<K, V>> {
-        self.curr
+        Shared::from(self.curr as *const _)
     }
 
     /// Clean up a chain of logically removed nodes in each traversal.
@@ -106,21 +321,21 @@ where
         let mut prev_next = self.curr;
         let found = loop {
             let curr_node = some_or!(unsafe { self.curr.as_ref() }, break false);
-            let next = curr_node.next.load(Ordering::Acquire, guard);
+            let next = curr_node.next.load(Ordering::Acquire);
 
             // - finding stage is done if cursor.curr advancement stops
             // - advance cursor.curr if (.next is marked) || (cursor.curr < key)
             // - stop cursor.curr if (not marked) && (cursor.curr >= key)
             // - advance cursor.prev if not marked
 
-            if next.tag() != 0 {
-                self.curr = next.with_tag(0);
+            if tag(next) != 0 {
+                self.curr = with_tag(next, 0);
                 continue;
             }
 
             match curr_node.key.cmp(key) {
                 Less => {
-                    self.curr = next.with_tag(0);
+                    self.curr = next;
                     self.prev = &curr_node.next;
                     prev_next = next;
                 }
@@ -135,16 +350,17 @@ where
         }
 
         // cleanup marked nodes between prev and curr
-        self.prev
-            .compare_and_set(prev_next, self.curr, Ordering::Release, guard)
+        let _ = self
+            .prev
+            .compare_exchange(prev_next, self.curr, Ordering::Release, Ordering::Relaxed)
             .map_err(|_| ())?;
 
         // defer_destroy from cursor.prev.load() to cursor.curr (exclusive)
         let mut node = prev_next;
-        while node.with_tag(0) != self.curr {
+        while with_tag(node, 0) != self.curr {
             unsafe {
-                let next = node.as_ref().unwrap().next.load(Ordering::Acquire, guard);
-                guard.defer_destroy(node);
+                let next = (*node).next.load(Ordering::Acquire);
+                Epoch::retire(node, guard);
                 node = next;
             }
         }
@@ -152,51 +368,20 @@ where
         Ok(found)
     }
 
-    /// Clean up a single logically removed node in each traversal.
-    #[inline]
-    pub fn find_harris_michael(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
-        loop {
-            debug_assert_eq!(self.curr.tag(), 0);
-
-            let curr_node = some_or!(unsafe { self.curr.as_ref() }, return Ok(false));
-            let mut next = curr_node.next.load(Ordering::Acquire, guard);
-
-            if next.tag() != 0 {
-                next = next.with_tag(0);
-                self.prev
-                    .compare_and_set(self.curr, next, Ordering::Release, guard)
-                    .map_err(|_| ())?;
-                unsafe {
-                    guard.defer_destroy(self.curr);
-                }
-                self.curr = next;
-                continue;
-            }
-
-            match curr_node.key.cmp(key) {
-                Less => {
-                    self.prev = &curr_node.next;
-                    self.curr = next;
-                }
-                Equal => return Ok(true),
-                Greater => return Ok(false),
-            }
-        }
-    }
-
     /// Gotta go fast. Doesn't fail.
     #[inline]
     pub fn find_harris_herlihy_shavit(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
+        let _ = guard;
         Ok(loop {
             let curr_node = some_or!(unsafe { self.curr.as_ref() }, break false);
             match curr_node.key.cmp(key) {
                 Less => {
-                    self.curr = curr_node.next.load(Ordering::Acquire, guard);
+                    self.curr = with_tag(curr_node.next.load(Ordering::Acquire), 0);
                     // NOTE: unnecessary (this function is expected to be used only for `get`)
                     self.prev = &curr_node.next;
                     continue;
                 }
-                Equal => break curr_node.next.load(Ordering::Relaxed, guard).tag() == 0,
+                Equal => break tag(curr_node.next.load(Ordering::Relaxed)) == 0,
                 Greater => break false,
             }
         })
@@ -215,38 +400,15 @@ where
         node: Owned<Node<K, V>>,
         guard: &'g Guard,
     ) -> Result<(), Owned<Node<K, V>>> {
-        node.next.store(self.curr, Ordering::Relaxed);
-        match self
-            .prev
-            .compare_and_set(self.curr, node, Ordering::Release, guard)
-        {
-            Ok(node) => {
-                self.curr = node;
-                Ok(())
-            }
-            Err(e) => Err(e.new),
-        }
+        self.insert_node(node.into_box(), guard)
+            .map_err(Owned::from)
     }
 
     /// Deletes the current node.
     #[inline]
-    pub fn delete(self, guard: &'g Guard) -> Result<&'g V, ()> {
-        let curr_node = unsafe { self.curr.as_ref() }.unwrap();
-
-        let next = curr_node.next.fetch_or(1, Ordering::Relaxed, guard);
-        if next.tag() == 1 {
-            return Err(());
-        }
-
-        if self
-            .prev
-            .compare_and_set(self.curr, next, Ordering::Release, guard)
-            .is_ok()
-        {
-            unsafe { guard.defer_destroy(self.curr) };
-        }
-
-        Ok(&curr_node.value)
+    pub fn delete(mut self, guard: &'g Guard) -> Result<&'g V, ()> {
+        self.delete_node(guard)?;
+        Ok(unsafe { &(*self.curr).value })
     }
 }
 
@@ -254,27 +416,101 @@ impl<K, V> List<K, V>
 where
     K: Ord,
 {
-    /// Creates a new list.
+    /// Creates a new list reclaimed by [`Epoch`].
+    ///
+    /// Use `Default` for the other reclamation schemes.
     pub fn new() -> Self {
-        List {
-            head: Atomic::null(),
+        Self::default()
+    }
+
+    /// Returns an iterator over the entries that are not logically removed, in the order of keys.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, K, V> {
+        Iter {
+            curr: unsafe { self.head.load(Ordering::Acquire).as_ref() },
+            _guard: guard,
+        }
+    }
+
+    #[inline]
+    fn lookup<'g, F>(&'g self, key: &K, find: F, guard: &'g Guard) -> Option<&'g V>
+    where
+        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
+    {
+        let (found, cursor) = self.find(key, &find, guard);
+        if found {
+            cursor.lookup()
+        } else {
+            None
         }
     }
 
+    /// Omitted
+    pub fn harris_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        self.lookup(key, Cursor::find_harris, guard)
+    }
+
+    /// Omitted
+    pub fn harris_insert<'g>(&'g self, key: K, value: V, guard: &'g Guard) -> bool {
+        self.insert(key, value, Cursor::find_harris, guard)
+    }
+
+    /// Omitted
+    pub fn harris_delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        self.delete(key, Cursor::find_harris, guard)
+            .and_then(|cursor| cursor.lookup())
+    }
+
+    /// Omitted
+    pub fn harris_michael_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        self.lookup(key, Cursor::find_harris_michael, guard)
+    }
+
+    /// Omitted
+    pub fn harris_michael_delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        self.delete(key, Cursor::find_harris_michael, guard)
+            .and_then(|cursor| cursor.lookup())
+    }
+
+    /// Omitted
+    pub fn harris_herlihy_shavit_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        self.lookup(key, Cursor::find_harris_herlihy_shavit, guard)
+    }
+
+    /// Omitted
+    pub fn harris_herlihy_shavit_insert(&self, key: K, value: V, guard: &Guard) -> bool {
+        self.insert(key, value, Cursor::find_harris_michael, guard)
+    }
+
+    /// Omitted
+    pub fn harris_herlihy_shavit_delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        self.delete(key, Cursor::find_harris_michael, guard)
+            .and_then(|cursor| cursor.lookup())
+    }
+}
+
+impl<K, V, R> List<K, V, R>
+where
+    K: Ord,
+    R: Reclaim,
+{
     /// Creates the head cursor.
     #[inline]
-    pub fn head<'g>(&'g self, guard: &'g Guard) -> Cursor<'g, K, V> {
+    pub fn head<'g>(&'g self, guard: &'g R::Guard) -> Cursor<'g, K, V, R> {
+        let mut curr_shield = R::Shield::default();
+        let curr = R::protect(&self.head, &mut curr_shield, guard);
         Cursor {
             prev: &self.head,
-            curr: self.head.load(Ordering::Acquire, guard),
+            curr,
+            prev_shield: R::Shield::default(),
+            curr_shield,
         }
     }
 
     /// Finds a key using the given find strategy.
     #[inline]
-    fn find<'g, F>(&'g self, key: &K, find: &F, guard: &'g Guard) -> (bool, Cursor<'g, K, V>)
+    fn find<'g, F>(&'g self, key: &K, find: &F, guard: &'g R::Guard) -> (bool, Cursor<'g, K, V, R>)
     where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
     {
         loop {
             let mut cursor = self.head(guard);
@@ -285,98 +521,69 @@ where
     }
 
     #[inline]
-    fn lookup<'g, F>(&'g self, key: &K, find: F, guard: &'g Guard) -> Option<&'g V>
+    fn insert<'g, F>(&'g self, key: K, value: V, find: F, guard: &'g R::Guard) -> bool
     where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
     {
-        let (found, cursor) = self.find(key, &find, guard);
-        if found {
-            unsafe { cursor.curr.as_ref().map(|n| &n.value) }
-        } else {
-            None
-        }
-    }
-
-    #[inline]
-    fn insert<'g, F>(&'g self, key: K, value: V, find: F, guard: &'g Guard) -> bool
-    where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
-    {
-        let mut node = Owned::new(Node::new(key, value));
+        let mut node = Box::new(Node::new(key, value));
         loop {
             let (found, mut cursor) = self.find(&node.key, &find, guard);
             if found {
-                drop(node.into_box().into_value());
+                drop(node.into_value());
                 return false;
             }
 
-            match cursor.insert(node, guard) {
+            match cursor.insert_node(node, guard) {
                 Err(n) => node = n,
                 Ok(()) => return true,
             }
         }
     }
 
+    /// Deletes the key, and returns the cursor at the deleted node.
     #[inline]
-    fn delete<'g, F>(&'g self, key: &K, find: F, guard: &'g Guard) -> Option<&'g V>
+    fn delete<'g, F>(&'g self, key: &K, find: F, guard: &'g R::Guard) -> Option<Cursor<'g, K, V, R>>
     where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
     {
         loop {
-            let (found, cursor) = self.find(key, &find, guard);
+            let (found, mut cursor) = self.find(key, &find, guard);
             if !found {
                 return None;
             }
 
-            match cursor.delete(guard) {
-                Err(()) => continue,
-                Ok(value) => return Some(value),
+            if cursor.delete_node(guard).is_ok() {
+                return Some(cursor);
             }
         }
     }
 
-    /// Omitted
-    pub fn harris_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
-        self.lookup(key, Cursor::find_harris, guard)
-    }
-
-    /// Omitted
-    pub fn harris_insert<'g>(&'g self, key: K, value: V, guard: &'g Guard) -> bool {
-        self.insert(key, value, Cursor::find_harris, guard)
-    }
-
-    /// Omitted
-    pub fn harris_delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
-        self.delete(key, Cursor::find_harris, guard)
-    }
-
-    /// Omitted
-    pub fn harris_michael_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
-        self.lookup(key, Cursor::find_harris_michael, guard)
-    }
-
-    /// Omitted
-    pub fn harris_michael_insert(&self, key: K, value: V, guard: &Guard) -> bool {
-        self.insert(key, value, Cursor::find_harris_michael, guard)
-    }
-
-    /// Omitted
-    pub fn harris_michael_delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
-        self.delete(key, Cursor::find_harris_michael, guard)
-    }
-
-    /// Omitted
-    pub fn harris_herlihy_shavit_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
-        self.lookup(key, Cursor::find_harris_herlihy_shavit, guard)
+    /// Like `harris_michael_lookup`, but returns a clone of the value, since the value may be
+    /// freed once the cursor is dropped.
+    pub fn harris_michael_lookup_cloned(&self, key: &K, guard: &R::Guard) -> Option<V>
+    where
+        V: Clone,
+    {
+        let (found, cursor) = self.find(key, &Cursor::find_harris_michael, guard);
+        if found {
+            unsafe { cursor.curr.as_ref() }.map(|n| n.value.clone())
+        } else {
+            None
+        }
     }
 
     /// Omitted
-    pub fn harris_herlihy_shavit_insert(&self, key: K, value: V, guard: &Guard) -> bool {
+    pub fn harris_michael_insert(&self, key: K, value: V, guard: &R::Guard) -> bool {
         self.insert(key, value, Cursor::find_harris_michael, guard)
     }
 
-    /// Omitted
-    pub fn harris_herlihy_shavit_delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+    /// Like `harris_michael_delete`, but returns a clone of the value, since the value may be
+    /// freed once the cursor is dropped.
+    pub fn harris_michael_delete_cloned(&self, key: &K, guard: &R::Guard) -> Option<V>
+    where
+        V: Clone,
+    {
         self.delete(key, Cursor::find_harris_michael, guard)
+            .and_then(|cursor| unsafe { cursor.curr.as_ref() }.map(|n| n.value.clone()))
     }
 }
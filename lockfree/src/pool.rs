@@ -0,0 +1,122 @@
+//! Per-thread pools of node memory.
+//!
+//! Each thread keeps a freelist of memory blocks for each layout of nodes. A node is allocated from
+//! the freelist of the current thread if possible, and the memory of a reclaimed node is put back
+//! to the freelist of the thread that reclaims it, unless the freelist already holds `cap` blocks.
+//! Zero `cap` disables pooling.
+
+use core::alloc::Layout;
+use core::cell::RefCell;
+use core::ptr;
+use std::alloc;
+
+#[derive(Debug, Default)]
+struct Pools {
+    /// Freelists of memory blocks, one for each layout. There are only a few kinds of nodes, so
+    /// linear search suffices.
+    freelists: Vec<(Layout, Vec<*mut u8>)>,
+}
+
+impl Pools {
+    fn freelist(&mut self, layout: Layout) -> &mut Vec<*mut u8> {
+        let index = match self.freelists.iter().position(|(l, _)| *l == layout) {
+            Some(index) => index,
+            None => {
+                self.freelists.push((layout, Vec::new()));
+                self.freelists.len() - 1
+            }
+        };
+        &mut self.freelists[index].1
+    }
+}
+
+impl Drop for Pools {
+    fn drop(&mut self) {
+        for (layout, freelist) in self.freelists.drain(..) {
+            for block in freelist {
+                unsafe { alloc::dealloc(block, layout) };
+            }
+        }
+    }
+}
+
+thread_local! {
+    static POOLS: RefCell<Pools> = RefCell::new(Pools::default());
+}
+
+/// Returns uninitialized memory for a `T`, which can be freed by `Box::from_raw` or `release`.
+///
+/// `T` should not be zero-sized.
+pub(crate) fn alloc<T>(cap: usize) -> *mut T {
+    let layout = Layout::new::<T>();
+    if cap > 0 {
+        // The pool may be already destroyed if the current thread is exiting.
+        let block = POOLS
+            .try_with(|pools| pools.borrow_mut().freelist(layout).pop())
+            .ok()
+            .flatten();
+        if let Some(block) = block {
+            return block as *mut T;
+        }
+    }
+
+    let block = unsafe { alloc::alloc(layout) };
+    if block.is_null() {
+        alloc::handle_alloc_error(layout);
+    }
+    block as *mut T
+}
+
+/// Drops the `T` pointed to by `ptr`, and puts its memory back to the current thread's pool if the
+/// pool holds less than `cap` blocks of its layout.
+///
+/// # Safety
+///
+/// `ptr` should be allocated by `alloc` or `Box`, and no longer accessed by the other threads.
+pub(crate) unsafe fn release<T>(ptr: *mut T, cap: usize) {
+    let layout = Layout::new::<T>();
+    ptr::drop_in_place(ptr);
+    let pooled = cap > 0
+        && POOLS
+            .try_with(|pools| {
+                let mut pools = pools.borrow_mut();
+                let freelist = pools.freelist(layout);
+                if freelist.len() < cap {
+                    freelist.push(ptr as *mut u8);
+                    true
+                } else {
+                    false
+                }
+            })
+            .unwrap_or(false);
+    if !pooled {
+        alloc::dealloc(ptr as *mut u8, layout);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn recycle_up_to_cap() {
+        let blocks = (0..3).map(|_| alloc::<[u64; 3]>(2)).collect::<Vec<_>>();
+        for &block in &blocks {
+            unsafe {
+                block.write([0; 3]);
+                release(block, 2);
+            }
+        }
+
+        // Only the first two blocks are pooled, and they are reused in the LIFO order.
+        let reused = (0..3).map(|_| alloc::<[u64; 3]>(2)).collect::<Vec<_>>();
+        assert_eq!(reused[0], blocks[1]);
+        assert_eq!(reused[1], blocks[0]);
+        for block in reused {
+            unsafe {
+                block.write([0; 3]);
+                release(block, 0);
+            }
+        }
+    }
+}
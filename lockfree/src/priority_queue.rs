@@ -0,0 +1,543 @@
+//! Lock-free skip-list-based priority queue.
+//!
+//! Usable with any number of producers and consumers.
+//!
+//! Lindén and Jonsson.  A Skiplist-Based Concurrent Priority Queue with Minimal Memory
+//! Contention.  OPODIS 2013.  https://doi.org/10.1007/978-3-319-03850-6_15
+
+use core::cell::Cell;
+use core::mem::ManuallyDrop;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+
+/// The maximum height of a node.
+const MAX_HEIGHT: usize = 32;
+
+/// The number of logically deleted nodes in the prefix of the list that `pop_min` tolerates before
+/// trying to physically delete them.
+const BOUND_OFFSET: usize = 32;
+
+/// Lindén-Jonsson priority queue.
+///
+/// Values with smaller priorities are popped first. Values with the same priority are popped in
+/// an unspecified order.
+// The representation is a skip list sorted by priority, except that the logically deleted nodes
+// form a prefix of the bottom level. A node is logically deleted by setting the tag of the pointer
+// to it in the bottom level, so only the bottom level `next` pointers are tagged. `pop_min` deletes
+// the first non-deleted node, and once the prefix gets longer than `BOUND_OFFSET`, it unlinks the
+// prefix from `head` in a batch.
+#[derive(Debug)]
+pub struct PriorityQueue<P, V> {
+    head: [Atomic<Node<P, V>>; MAX_HEIGHT],
+}
+
+#[derive(Debug)]
+struct Node<P, V> {
+    priority: P,
+
+    /// Moved out by the `pop_min` that logically deletes this node.
+    value: ManuallyDrop<V>,
+
+    /// Set while the node is being linked in the upper levels. The nodes after such a node are not
+    /// unlinked from `head`, because the insertion may still access them.
+    inserting: AtomicBool,
+
+    /// Mark: tag() of `next[0]` means that the next node is logically deleted.
+    next: Box<[Atomic<Node<P, V>>]>,
+}
+
+// `P` is read by all threads and `V` is moved out by one thread.
+unsafe impl<P: Send + Sync, V: Send> Sync for PriorityQueue<P, V> {}
+unsafe impl<P: Send + Sync, V: Send> Send for PriorityQueue<P, V> {}
+
+impl<P, V> Default for PriorityQueue<P, V> {
+    fn default() -> Self {
+        Self {
+            head: Default::default(),
+        }
+    }
+}
+
+/// Returns a random height with geometric distribution.
+fn random_height() -> usize {
+    thread_local! {
+        static SEED: Cell<u32> = Cell::new(RandomState::new().build_hasher().finish() as u32 | 1);
+    }
+
+    SEED.with(|seed| {
+        // xorshift32
+        let mut x = seed.get();
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        seed.set(x);
+        (x.trailing_zeros() as usize + 1).min(MAX_HEIGHT)
+    })
+}
+
+impl<P: Ord, V> PriorityQueue<P, V> {
+    /// Creates a new, empty priority queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finds the predecessors and successors of `priority` at each level, skipping the logically
+    /// deleted nodes. Returns the last logically deleted node (or null if there is none).
+    fn locate_preds<'g>(
+        &'g self,
+        priority: &P,
+        preds: &mut [&'g [Atomic<Node<P, V>>]; MAX_HEIGHT],
+        succs: &mut [Shared<'g, Node<P, V>>; MAX_HEIGHT],
+        guard: &'g Guard,
+    ) -> Shared<'g, Node<P, V>> {
+        let mut pred = &self.head[..];
+        let mut del = Shared::null();
+
+        for i in (0..MAX_HEIGHT).rev() {
+            let next = pred[i].load(Ordering::Acquire, guard);
+            let mut deleted = next.tag() != 0;
+            let mut curr = next.with_tag(0);
+
+            // Advances if `curr` is smaller than `priority` or logically deleted. A node whose
+            // next node is logically deleted is also logically deleted.
+            while let Some(curr_ref) = unsafe { curr.as_ref() } {
+                if !(curr_ref.priority < *priority
+                    || curr_ref.next[0].load(Ordering::Acquire, guard).tag() != 0
+                    || (i == 0 && deleted))
+                {
+                    break;
+                }
+
+                if i == 0 && deleted {
+                    del = curr;
+                }
+                pred = &curr_ref.next;
+                let next = pred[i].load(Ordering::Acquire, guard);
+                deleted = next.tag() != 0;
+                curr = next.with_tag(0);
+            }
+
+            preds[i] = pred;
+            succs[i] = curr;
+        }
+
+        del
+    }
+
+    /// Unlinks the logically deleted nodes from `head` in the upper levels.
+    fn restructure(&self, guard: &Guard) {
+        let mut pred = &self.head[..];
+        let mut i = MAX_HEIGHT - 1;
+
+        while i > 0 {
+            let h = self.head[i].load(Ordering::Acquire, guard);
+            let h_ref = some_or!(unsafe { h.as_ref() }, {
+                i -= 1;
+                continue;
+            });
+            if h_ref.next[0].load(Ordering::Acquire, guard).tag() == 0 {
+                i -= 1;
+                continue;
+            }
+
+            let mut curr = pred[i].load(Ordering::Acquire, guard);
+            while let Some(curr_ref) = unsafe { curr.as_ref() } {
+                if curr_ref.next[0].load(Ordering::Acquire, guard).tag() == 0 {
+                    break;
+                }
+                pred = &curr_ref.next;
+                curr = pred[i].load(Ordering::Acquire, guard);
+            }
+
+            if self.head[i]
+                .compare_and_set(h, curr, Ordering::Release, guard)
+                .is_ok()
+            {
+                i -= 1;
+            }
+        }
+    }
+
+    /// Adds `value` with the given `priority` to the queue.
+    pub fn push(&self, priority: P, value: V, guard: &Guard) {
+        let height = random_height();
+        let new = Owned::new(Node {
+            priority,
+            value: ManuallyDrop::new(value),
+            inserting: AtomicBool::new(true),
+            next: (0..height).map(|_| Atomic::null()).collect(),
+        })
+        .into_shared(guard);
+        let new_ref = unsafe { new.deref() };
+
+        let mut preds = [&self.head[..]; MAX_HEIGHT];
+        let mut succs = [Shared::null(); MAX_HEIGHT];
+
+        // Links the node in the bottom level, which linearizes the insertion.
+        let mut del = loop {
+            let del = self.locate_preds(&new_ref.priority, &mut preds, &mut succs, guard);
+            new_ref.next[0].store(succs[0], Ordering::Relaxed);
+            if preds[0][0]
+                .compare_and_set(succs[0], new, Ordering::Release, guard)
+                .is_ok()
+            {
+                break del;
+            }
+        };
+
+        // Links the node in the upper levels. Gives up if the node or its successor gets deleted,
+        // because otherwise the node may point to an unlinked node.
+        let mut i = 1;
+        while i < height {
+            new_ref.next[i].store(succs[i], Ordering::Relaxed);
+
+            let succ_deleted = match unsafe { succs[i].as_ref() } {
+                Some(succ) => succ.next[0].load(Ordering::Acquire, guard).tag() != 0,
+                None => false,
+            };
+            if new_ref.next[0].load(Ordering::Acquire, guard).tag() != 0
+                || succ_deleted
+                || (!del.is_null() && del == succs[i])
+            {
+                break;
+            }
+
+            if preds[i][i]
+                .compare_and_set(succs[i], new, Ordering::Release, guard)
+                .is_ok()
+            {
+                i += 1;
+            } else {
+                del = self.locate_preds(&new_ref.priority, &mut preds, &mut succs, guard);
+                if succs[0] != new {
+                    break;
+                }
+            }
+        }
+
+        new_ref.inserting.store(false, Ordering::Release);
+    }
+
+    /// Attempts to pop a value with the smallest priority.
+    ///
+    /// Returns `None` if the queue is observed to be empty.
+    pub fn pop_min(&self, guard: &Guard) -> Option<(P, V)>
+    where
+        P: Clone,
+    {
+        let obs_head = self.head[0].load(Ordering::Acquire, guard);
+
+        // Logically deletes the first non-deleted node.
+        let mut pred = &self.head[..];
+        let mut new_head = Shared::null();
+        let mut offset = 0;
+        let node = loop {
+            if pred[0].load(Ordering::Acquire, guard).with_tag(0).is_null() {
+                return None;
+            }
+
+            let next = pred[0].fetch_or(1, Ordering::AcqRel, guard);
+            offset += 1;
+            if next.tag() == 0 {
+                break next;
+            }
+
+            let next_ref = unsafe { next.deref() };
+            if new_head.is_null() && next_ref.inserting.load(Ordering::Acquire) {
+                new_head = next.with_tag(0);
+            }
+            pred = &next_ref.next;
+        };
+
+        let node_ref = unsafe { node.deref() };
+        let result = (node_ref.priority.clone(), unsafe {
+            ManuallyDrop::into_inner(ptr::read(&node_ref.value))
+        });
+
+        if offset < BOUND_OFFSET {
+            return Some(result);
+        }
+
+        // Unlinks the prefix of deleted nodes before `new_head` from `head`.
+        if new_head.is_null() {
+            new_head = node;
+        }
+        if self.head[0]
+            .compare_and_set(obs_head, new_head.with_tag(1), Ordering::Release, guard)
+            .is_ok()
+        {
+            self.restructure(guard);
+
+            let mut curr = obs_head.with_tag(0);
+            while curr != new_head {
+                unsafe {
+                    let next = curr.deref().next[0].load(Ordering::Relaxed, guard);
+                    guard.defer_destroy(curr);
+                    curr = next.with_tag(0);
+                }
+            }
+        }
+
+        Some(result)
+    }
+}
+
+impl<P, V> Drop for PriorityQueue<P, V> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut curr = self.head[0].load(Ordering::Relaxed, guard);
+            while let Some(curr_ref) = curr.with_tag(0).as_ref() {
+                let next = curr_ref.next[0].load(Ordering::Relaxed, guard);
+                let mut node = curr.with_tag(0).into_owned();
+                if curr.tag() == 0 {
+                    ManuallyDrop::drop(&mut node.value);
+                }
+                drop(node);
+                curr = next;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_epoch::pin;
+    use crossbeam_utils::thread::scope;
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn push_pop_seq() {
+        let q = PriorityQueue::new();
+        let guard = &pin();
+        assert!(q.pop_min(guard).is_none());
+        for &i in &[5, 1, 4, 1, 3] {
+            q.push(i, i * 10, guard);
+        }
+        assert_eq!(q.pop_min(guard), Some((1, 10)));
+        assert_eq!(q.pop_min(guard), Some((1, 10)));
+        assert_eq!(q.pop_min(guard), Some((3, 30)));
+        q.push(2, 20, guard);
+        assert_eq!(q.pop_min(guard), Some((2, 20)));
+        assert_eq!(q.pop_min(guard), Some((4, 40)));
+        assert_eq!(q.pop_min(guard), Some((5, 50)));
+        assert!(q.pop_min(guard).is_none());
+    }
+
+    /// Sequential operations agree with `BinaryHeap`.
+    #[test]
+    fn binary_heap_seq() {
+        let q = PriorityQueue::new();
+        let mut heap = BinaryHeap::new();
+        let mut rng = 0x2545_f491u32;
+        for _ in 0..100_000 {
+            rng ^= rng << 13;
+            rng ^= rng >> 17;
+            rng ^= rng << 5;
+            let guard = &pin();
+            match rng % 3 {
+                0 => assert_eq!(
+                    q.pop_min(guard).map(|(p, _)| p),
+                    heap.pop().map(|Reverse(p)| p)
+                ),
+                _ => {
+                    let p = rng % 1000;
+                    q.push(p, (), guard);
+                    heap.push(Reverse(p));
+                }
+            }
+        }
+        while let Some(Reverse(p)) = heap.pop() {
+            assert_eq!(q.pop_min(&pin()).map(|(p, _)| p), Some(p));
+        }
+        assert!(q.pop_min(&pin()).is_none());
+    }
+
+    /// Concurrent pushes followed by concurrent pops. Since no push is concurrent with the pops,
+    /// each thread must pop in non-decreasing order, and all values are popped exactly once.
+    #[test]
+    fn push_then_pop_concurrent() {
+        const THREADS: usize = 8;
+        const COUNT: usize = 20_000;
+
+        let q = PriorityQueue::new();
+        scope(|s| {
+            for t in 0..THREADS {
+                let q = &q;
+                s.spawn(move |_| {
+                    for i in 0..COUNT {
+                        let v = (i * 7919 + t) % (THREADS * COUNT);
+                        q.push(v, v, &pin());
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        let popped = scope(|s| {
+            let mut handles = Vec::new();
+            for _ in 0..THREADS {
+                handles.push(s.spawn(|_| {
+                    let mut popped = Vec::new();
+                    while let Some((p, v)) = q.pop_min(&pin()) {
+                        assert_eq!(p, v);
+                        if let Some(&last) = popped.last() {
+                            assert!(last <= p);
+                        }
+                        popped.push(p);
+                    }
+                    popped
+                }));
+            }
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+
+        let mut expected = (0..THREADS)
+            .flat_map(|t| (0..COUNT).map(move |i| (i * 7919 + t) % (THREADS * COUNT)))
+            .collect::<Vec<_>>();
+        expected.sort_unstable();
+        let mut popped = popped;
+        popped.sort_unstable();
+        assert_eq!(popped, expected);
+    }
+
+    #[test]
+    fn push_pop_concurrent() {
+        const THREADS: usize = 8;
+        const COUNT: usize = 20_000;
+
+        let q = PriorityQueue::new();
+        let sum = scope(|s| {
+            let mut handles = Vec::new();
+            for t in 0..THREADS {
+                let q = &q;
+                handles.push(s.spawn(move |_| {
+                    let mut sum = 0;
+                    for i in 0..COUNT {
+                        q.push((i + t) % 100, i, &pin());
+                        sum += q.pop_min(&pin()).unwrap().1;
+                    }
+                    sum
+                }));
+            }
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .sum::<usize>()
+        })
+        .unwrap();
+
+        assert!(q.pop_min(&pin()).is_none());
+        assert_eq!(sum, THREADS * COUNT * (COUNT - 1) / 2);
+    }
+
+    /// Concurrent pushes and pops with timestamped histories. A key that is pushed before a
+    /// `pop_min` is invoked and popped only by a `pop_min` invoked after it returns is present
+    /// throughout the call, so the call must return a priority that is not greater than the key's.
+    #[test]
+    fn pop_min_bounded_concurrent() {
+        const THREADS: usize = 4;
+        const COUNT: usize = 2_000;
+
+        struct Pop {
+            start: usize,
+            end: usize,
+            popped: Option<(usize, usize)>,
+        }
+
+        let q = PriorityQueue::new();
+        let clock = AtomicUsize::new(0);
+        let histories = scope(|s| {
+            let mut handles = Vec::new();
+            for t in 0..THREADS {
+                let (q, clock) = (&q, &clock);
+                handles.push(s.spawn(move |_| {
+                    let mut pushes = Vec::new();
+                    let mut pops = Vec::new();
+                    let mut rng = 0x2545_f491u32 + t as u32;
+                    for i in 0..COUNT {
+                        rng ^= rng << 13;
+                        rng ^= rng >> 17;
+                        rng ^= rng << 5;
+                        if rng & 1 == 0 {
+                            let key = t * COUNT + i;
+                            let p = (rng % 1000) as usize;
+                            q.push(p, key, &pin());
+                            pushes.push((key, p, clock.fetch_add(1, Ordering::SeqCst)));
+                        } else {
+                            let start = clock.fetch_add(1, Ordering::SeqCst);
+                            let popped = q.pop_min(&pin());
+                            let end = clock.fetch_add(1, Ordering::SeqCst);
+                            pops.push(Pop { start, end, popped });
+                        }
+                    }
+                    (pushes, pops)
+                }));
+            }
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+
+        // The start of the `pop_min` that popped each key.
+        let mut removed = vec![usize::MAX; THREADS * COUNT];
+        for pop in histories.iter().flat_map(|(_, pops)| pops) {
+            if let Some((_, key)) = pop.popped {
+                assert_eq!(removed[key], usize::MAX);
+                removed[key] = pop.start;
+            }
+        }
+        for pop in histories.iter().flat_map(|(_, pops)| pops) {
+            let bound = histories
+                .iter()
+                .flat_map(|(pushes, _)| pushes)
+                .filter(|&&(key, _, pushed)| pushed < pop.start && removed[key] > pop.end)
+                .map(|&(_, p, _)| p)
+                .min();
+            if let Some(bound) = bound {
+                let (p, _) = pop
+                    .popped
+                    .expect("pop_min returned None for a non-empty queue");
+                assert!(p <= bound);
+            }
+        }
+    }
+
+    #[test]
+    fn drop_remaining() {
+        struct Canary<'a>(&'a AtomicUsize);
+
+        impl Drop for Canary<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let dropped = AtomicUsize::new(0);
+        {
+            let q = PriorityQueue::new();
+            let guard = &pin();
+            for i in 0..(4 * BOUND_OFFSET) {
+                q.push(i, Canary(&dropped), guard);
+            }
+            for _ in 0..(2 * BOUND_OFFSET) {
+                drop(q.pop_min(guard));
+            }
+            assert_eq!(dropped.load(Ordering::Relaxed), 2 * BOUND_OFFSET);
+        }
+        assert_eq!(dropped.load(Ordering::Relaxed), 4 * BOUND_OFFSET);
+    }
+}
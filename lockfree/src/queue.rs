@@ -5,21 +5,29 @@
 //! Michael and Scott.  Simple, Fast, and Practical Non-Blocking and Blocking Concurrent Queue
 //! Algorithms.  PODC 1996.  http://dl.acm.org/citation.cfm?id=248106
 
+use core::marker::PhantomData;
 use core::mem::MaybeUninit;
 use core::ptr;
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{AtomicPtr, Ordering};
 
-use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
 use crossbeam_utils::CachePadded;
 
+use crate::pool;
+use crate::reclaim::{Epoch, Reclaim};
+
 /// Michael-Scott queue.
+///
+/// Popped nodes are reclaimed by the scheme `R`.
 // The representation here is a singly-linked list, with a sentinel node at the front. In general
 // the `tail` pointer may lag behind the actual tail. Non-sentinel nodes are either all `Data` or
 // all `Blocked` (requests for data from blocked threads).
 #[derive(Debug)]
-pub struct Queue<T> {
-    head: CachePadded<Atomic<Node<T>>>,
-    tail: CachePadded<Atomic<Node<T>>>,
+pub struct Queue<T, R: Reclaim = Epoch> {
+    head: CachePadded<AtomicPtr<Node<T>>>,
+    tail: CachePadded<AtomicPtr<Node<T>>>,
+    /// The maximum number of nodes in each thread's pool. Zero if pooling is disabled.
+    pool_cap: usize,
+    _marker: PhantomData<R>,
 }
 
 #[derive(Debug)]
@@ -32,76 +40,87 @@ struct Node<T> {
     /// out. After that such empty nodes get added to the collector for destruction.
     data: MaybeUninit<T>,
 
-    next: Atomic<Node<T>>,
+    next: AtomicPtr<Node<T>>,
 }
 
 // Any particular `T` should never be accessed concurrently, so no need for `Sync`.
-unsafe impl<T: Send> Sync for Queue<T> {}
-unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send, R: Reclaim> Sync for Queue<T, R> {}
+unsafe impl<T: Send, R: Reclaim> Send for Queue<T, R> {}
 
-impl<T> Default for Queue<T> {
+impl<T, R: Reclaim> Default for Queue<T, R> {
     fn default() -> Self {
-        let q = Self {
-            head: CachePadded::new(Atomic::null()),
-            tail: CachePadded::new(Atomic::null()),
-        };
-        // TODO(taiki-e): when the minimum supported Rust version is bumped to 1.36+,
-        // replace this with `mem::MaybeUninit`.
-        #[allow(deprecated)]
-        let sentinel = Owned::new(Node {
+        let sentinel = Box::into_raw(Box::new(Node {
             data: MaybeUninit::uninit(),
-            next: Atomic::null(),
-        });
-        unsafe {
-            let guard = &unprotected();
-            let sentinel = sentinel.into_shared(guard);
-            q.head.store(sentinel, Ordering::Relaxed);
-            q.tail.store(sentinel, Ordering::Relaxed);
-            q
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        Self {
+            head: CachePadded::new(AtomicPtr::new(sentinel)),
+            tail: CachePadded::new(AtomicPtr::new(sentinel)),
+            pool_cap: 0,
+            _marker: PhantomData,
         }
     }
 }
 
 impl<T> Queue<T> {
-    /// Create a new, empty queue.
+    /// Create a new, empty queue reclaimed by [`Epoch`].
+    ///
+    /// Use `Default` for the other reclamation schemes.
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Creates a new, empty queue that recycles nodes.
+    ///
+    /// Once reclaimed, the popped nodes are put back to the per-thread node pool, which holds at
+    /// most `pool_cap` nodes, instead of being freed, and pushes take nodes from the pool.
+    pub fn with_pool(pool_cap: usize) -> Self {
+        let mut q = Self::default();
+        q.pool_cap = pool_cap;
+        q
+    }
+}
+
+impl<T, R: Reclaim> Queue<T, R> {
     /// Adds `t` to the back of the queue, possibly waking up threads blocked on `pop`.
-    pub fn push(&self, t: T, guard: &Guard) {
-        let new = Owned::new(Node {
-            data: MaybeUninit::new(t),
-            next: Atomic::null(),
-        });
-        let new = Owned::into_shared(new, guard);
+    pub fn push(&self, t: T, guard: &R::Guard) {
+        let new = pool::alloc::<Node<T>>(self.pool_cap);
+        unsafe {
+            new.write(Node {
+                data: MaybeUninit::new(t),
+                next: AtomicPtr::new(ptr::null_mut()),
+            })
+        };
+        let mut shield = R::Shield::default();
 
         loop {
-            // We push onto the tail, so we'll start optimistically by looking there first.
-            let tail = self.tail.load(Ordering::Acquire, guard);
+            // We push onto the tail, so we'll start optimistically by looking there first. `tail`
+            // never lags behind `head`, so it is not retired while it is protected and still the
+            // tail.
+            let tail = R::protect(&self.tail, &mut shield, guard);
 
             // Attempt to push onto the `tail` snapshot; fails if `tail.next` has changed.
-            let tail_ref = unsafe { tail.deref() };
-            let next = tail_ref.next.load(Ordering::Acquire, guard);
+            let tail_ref = unsafe { &*tail };
+            let next = tail_ref.next.load(Ordering::Acquire);
 
             // If `tail` is not the actual tail, try to "help" by moving the tail pointer forward.
             if !next.is_null() {
-                let _ = self
-                    .tail
-                    .compare_and_set(tail, next, Ordering::Release, guard);
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
                 continue;
             }
 
             // looks like the actual tail; attempt to link at `tail.next`.
             if tail_ref
                 .next
-                .compare_and_set(Shared::null(), new, Ordering::Release, guard)
+                .compare_exchange(ptr::null_mut(), new, Ordering::Release, Ordering::Relaxed)
                 .is_ok()
             {
                 // try to move the tail pointer forward.
                 let _ = self
                     .tail
-                    .compare_and_set(tail, new, Ordering::Release, guard);
+                    .compare_exchange(tail, new, Ordering::Release, Ordering::Relaxed);
                 break;
             }
         }
@@ -110,46 +129,111 @@ impl<T> Queue<T> {
     /// Attempts to dequeue from the front.
     ///
     /// Returns `None` if the queue is observed to be empty.
-    pub fn try_pop(&self, guard: &Guard) -> Option<T> {
+    pub fn try_pop(&self, guard: &R::Guard) -> Option<T> {
+        let mut head_shield = R::Shield::default();
+        let mut next_shield = R::Shield::default();
         loop {
-            let head = self.head.load(Ordering::Acquire, guard);
-            let h = unsafe { head.deref() };
-            let next = h.next.load(Ordering::Acquire, guard);
+            let head = R::protect(&self.head, &mut head_shield, guard);
+            let h = unsafe { &*head };
+            let next = R::protect(&h.next, &mut next_shield, guard);
+
+            // `next` is retired only after `head` is, so if `head` is still the head, then `next`
+            // was not retired when protected.
+            if head != self.head.load(Ordering::Acquire) {
+                continue;
+            }
             let next_ref = some_or!(unsafe { next.as_ref() }, return None);
 
             // Moves `tail` if it's stale. Relaxed load is enough because if tail == head, then the
             // messages for that node are already acquired.
-            let tail = self.tail.load(Ordering::Relaxed, guard);
+            let tail = self.tail.load(Ordering::Relaxed);
             if tail == head {
-                let _ = self
-                    .tail
-                    .compare_and_set(tail, next, Ordering::Release, guard);
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
             }
 
             if self
                 .head
-                .compare_and_set(head, next, Ordering::Release, guard)
+                .compare_exchange(head, next, Ordering::Release, Ordering::Relaxed)
                 .is_ok()
             {
                 unsafe {
-                    guard.defer_destroy(head);
+                    R::retire_to_pool(head, self.pool_cap, guard);
                     return Some(ptr::read(&next_ref.data).assume_init());
                 }
             }
         }
     }
+
+    /// Returns an iterator that pops elements until the queue is observed to be empty.
+    pub fn drain<'a>(&'a self, guard: &'a R::Guard) -> Drain<'a, T, R> {
+        Drain { queue: self, guard }
+    }
 }
 
-impl<T> Drop for Queue<T> {
-    fn drop(&mut self) {
-        unsafe {
-            let guard = unprotected();
+/// A draining iterator of a [`Queue`], created by [`Queue::drain`].
+#[derive(Debug)]
+pub struct Drain<'a, T, R: Reclaim = Epoch> {
+    queue: &'a Queue<T, R>,
+    guard: &'a R::Guard,
+}
+
+impl<T, R: Reclaim> Iterator for Drain<'_, T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.queue.try_pop(self.guard)
+    }
+}
+
+/// An owning iterator of a [`Queue`], which yields the elements from front to back.
+#[derive(Debug)]
+pub struct IntoIter<T, R: Reclaim = Epoch> {
+    queue: Queue<T, R>,
+}
+
+impl<T, R: Reclaim> Iterator for IntoIter<T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // The queue is owned, so the old sentinel is freed right away, and the node of the popped
+        // value becomes the new sentinel. The rest is freed by `Queue::drop`.
+        let head = self.queue.head.get_mut();
+        let next = unsafe { *(**head).next.get_mut() };
+        if next.is_null() {
+            return None;
+        }
+        let tail = self.queue.tail.get_mut();
+        if *tail == *head {
+            *tail = next;
+        }
+        unsafe { drop(Box::from_raw(*head)) };
+        *head = next;
+        Some(unsafe { ptr::read((*next).data.as_ptr()) })
+    }
+}
 
-            while self.try_pop(guard).is_some() {}
+impl<T, R: Reclaim> IntoIterator for Queue<T, R> {
+    type Item = T;
+    type IntoIter = IntoIter<T, R>;
 
-            // Destroy the remaining sentinel node.
-            let sentinel = self.head.load(Ordering::Relaxed, guard);
-            drop(sentinel.into_owned());
+    fn into_iter(self) -> IntoIter<T, R> {
+        IntoIter { queue: self }
+    }
+}
+
+impl<T, R: Reclaim> Drop for Queue<T, R> {
+    fn drop(&mut self) {
+        unsafe {
+            // The first node is the sentinel, and the others contain values.
+            let sentinel = Box::from_raw(*self.head.get_mut());
+            let mut node = sentinel.next.load(Ordering::Relaxed);
+            while !node.is_null() {
+                let mut n = Box::from_raw(node);
+                ptr::drop_in_place(n.data.as_mut_ptr());
+                node = *n.next.get_mut();
+            }
         }
     }
 }
@@ -177,10 +261,10 @@ mod test {
         }
 
         pub fn is_empty(&self) -> bool {
-            let guard = &pin();
-            let head = self.queue.head.load(Ordering::Acquire, guard);
-            let h = unsafe { head.deref() };
-            h.next.load(Ordering::Acquire, guard).is_null()
+            let _guard = &pin();
+            let head = self.queue.head.load(Ordering::Acquire);
+            let h = unsafe { &*head };
+            h.next.load(Ordering::Acquire).is_null()
         }
 
         pub fn try_pop(&self) -> Option<T> {
@@ -402,4 +486,73 @@ mod test {
         assert!(!q.is_empty());
         assert!(q.try_pop().is_some());
     }
+
+    #[test]
+    fn iterators() {
+        let q = super::Queue::new();
+        let guard = &pin();
+        for i in 0..10 {
+            q.push(i, guard);
+        }
+        assert_eq!(q.drain(guard).take(3).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(
+            q.into_iter().collect::<Vec<_>>(),
+            (3..10).collect::<Vec<_>>()
+        );
+
+        let q = super::Queue::new();
+        assert_eq!(q.drain(guard).next(), None);
+        q.push(0, guard);
+        let mut iter = q.into_iter();
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iterators_drop_once() {
+        use core::sync::atomic::AtomicUsize;
+
+        struct Canary<'a>(&'a AtomicUsize);
+
+        impl Drop for Canary<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let dropped = AtomicUsize::new(0);
+        {
+            let q = super::Queue::new();
+            let guard = &pin();
+            for _ in 0..10 {
+                q.push(Canary(&dropped), guard);
+            }
+            q.drain(guard).take(3).for_each(drop);
+            assert_eq!(dropped.load(Ordering::Relaxed), 3);
+
+            let mut iter = q.into_iter();
+            drop(iter.next());
+            assert_eq!(dropped.load(Ordering::Relaxed), 4);
+        }
+        assert_eq!(dropped.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn push_try_pop_pool() {
+        let q = super::Queue::with_pool(64);
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|_| {
+                    for i in 0..10_000 {
+                        let guard = &pin();
+                        q.push(i, guard);
+                        assert!(q.try_pop(guard).is_some());
+                    }
+                });
+            }
+        })
+        .unwrap();
+        assert!(q.try_pop(&pin()).is_none());
+    }
 }
@@ -0,0 +1,152 @@
+//! Memory reclamation schemes.
+//!
+//! A data structure generic over [`Reclaim`] stores its links as plain `AtomicPtr`s and leaves it
+//! to the scheme to decide when an unlinked node can be freed. This crate provides the
+//! epoch-based scheme [`Epoch`]; other schemes, e.g. hazard pointers, can be plugged in from
+//! outside of this crate.
+
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use crossbeam_epoch::{Guard, Shared};
+
+use crate::pool;
+
+/// A safe memory reclamation scheme.
+///
+/// A thread accesses shared nodes only while holding a guard. Before dereferencing a pointer
+/// loaded from a shared location, the thread should `protect` it with a shield, which prevents
+/// the node from being freed while the shield protects it. Once a node is unlinked, it is
+/// `retire`d, and the scheme frees it when no thread can access it any more.
+pub trait Reclaim {
+    /// The per-operation state that should be held while accessing shared nodes.
+    type Guard;
+
+    /// The protection of a single pointer.
+    ///
+    /// The default value protects nothing.
+    type Shield: Default;
+
+    /// Returns a guard for the current thread.
+    fn pin() -> Self::Guard;
+
+    /// Loads a pointer from `src` and protects it with `shield`, releasing the pointer previously
+    /// protected by `shield`.
+    ///
+    /// The returned pointer was stored in `src` after the protection took effect, so it can be
+    /// dereferenced as long as `guard` is alive and `shield` is neither dropped nor reused, if
+    /// nodes are retired only after being unlinked from `src`.
+    fn protect<T>(src: &AtomicPtr<T>, shield: &mut Self::Shield, guard: &Self::Guard) -> *mut T;
+
+    /// Retires a node, which will be freed once no thread protects it.
+    ///
+    /// # Safety
+    ///
+    /// The node should be allocated with `Box`, unlinked from the data structure, and retired only
+    /// once.
+    unsafe fn retire<T>(ptr: *mut T, guard: &Self::Guard);
+
+    /// Retires a node like [`retire`](Reclaim::retire), but the memory of the node may be put back
+    /// to the node pool of the reclaiming thread, which holds at most `pool_cap` nodes of each
+    /// layout, instead of being freed.
+    ///
+    /// The default implementation ignores the pool.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`retire`](Reclaim::retire).
+    unsafe fn retire_to_pool<T>(ptr: *mut T, pool_cap: usize, guard: &Self::Guard) {
+        let _ = pool_cap;
+        Self::retire(ptr, guard)
+    }
+}
+
+/// Epoch-based reclamation by `crossbeam_epoch`.
+///
+/// A pinned guard protects every node that is not retired before pinning, so shields are no-op.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Epoch;
+
+impl Reclaim for Epoch {
+    type Guard = Guard;
+    type Shield = ();
+
+    fn pin() -> Guard {
+        crossbeam_epoch::pin()
+    }
+
+    fn protect<T>(src: &AtomicPtr<T>, _: &mut (), _: &Guard) -> *mut T {
+        src.load(Ordering::Acquire)
+    }
+
+    unsafe fn retire<T>(ptr: *mut T, guard: &Guard) {
+        guard.defer_destroy(Shared::from(ptr as *const T));
+    }
+
+    unsafe fn retire_to_pool<T>(ptr: *mut T, pool_cap: usize, guard: &Guard) {
+        if pool_cap == 0 {
+            Self::retire(ptr, guard)
+        } else {
+            guard.defer_unchecked(move || pool::release(ptr, pool_cap))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{List, Queue, Stack};
+    use core::sync::atomic::AtomicUsize;
+
+    static RETIRED: AtomicUsize = AtomicUsize::new(0);
+
+    /// `Epoch` that counts the retired nodes.
+    #[derive(Debug, Default)]
+    struct Counting;
+
+    impl Reclaim for Counting {
+        type Guard = Guard;
+        type Shield = ();
+
+        fn pin() -> Guard {
+            Epoch::pin()
+        }
+
+        fn protect<T>(src: &AtomicPtr<T>, shield: &mut (), guard: &Guard) -> *mut T {
+            Epoch::protect(src, shield, guard)
+        }
+
+        unsafe fn retire<T>(ptr: *mut T, guard: &Guard) {
+            let _ = RETIRED.fetch_add(1, Ordering::Relaxed);
+            Epoch::retire(ptr, guard)
+        }
+    }
+
+    #[test]
+    fn retire_popped_nodes() {
+        let stack = Stack::<usize, Counting>::default();
+        for i in 0..10 {
+            stack.push(i);
+        }
+        assert_eq!(stack.pop(), Some(9));
+        assert_eq!(stack.pop_all().count(), 9);
+
+        let queue = Queue::<usize, Counting>::default();
+        let guard = &Counting::pin();
+        for i in 0..10 {
+            queue.push(i, guard);
+        }
+        while queue.try_pop(guard).is_some() {}
+
+        let list = List::<usize, usize, Counting>::default();
+        for i in 0..10 {
+            assert!(list.harris_michael_insert(i, i, guard));
+        }
+        for i in 0..10 {
+            assert_eq!(list.harris_michael_delete_cloned(&i, guard), Some(i));
+        }
+
+        // Every popped node of the stack, every old sentinel of the queue and every deleted node
+        // of the list is retired once.
+        assert_eq!(RETIRED.load(Ordering::Relaxed), 30);
+    }
+}
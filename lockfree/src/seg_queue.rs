@@ -0,0 +1,356 @@
+//! Fetch-and-add based lock-free queue.
+//!
+//! Usable with any number of producers and consumers.
+//!
+//! The queue is a Michael-Scott queue of segments, each of which is an array of slots. Producers
+//! and consumers claim slots by fetch-and-add on the segment's indices, so in the common case an
+//! operation does not retry on a contended CAS. Only when a segment is exhausted a new segment is
+//! linked (or unlinked) with a CAS, and unlinked segments are reclaimed via epoch.
+//!
+//! Ramalhete and Correia.  FAAArrayQueue.
+//! https://github.com/pramalhe/ConcurrencyFreaks/blob/master/papers/faaarrayqueue-2016.pdf
+
+use core::cell::UnsafeCell;
+use core::mem::{self, MaybeUninit};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+use crossbeam_utils::CachePadded;
+
+/// The number of slots in a segment.
+const SEGMENT_CAP: usize = 64;
+
+/// The slot is not written yet.
+const EMPTY: usize = 0;
+/// The slot contains a value.
+const WRITTEN: usize = 1;
+/// The slot is claimed by a consumer. If the value was not written yet, the producer will retry
+/// with another slot.
+const TAKEN: usize = 2;
+
+/// Fetch-and-add based segment queue.
+///
+/// Has the same interface as [`Queue`](crate::Queue), but scales better when many threads push
+/// and pop at the same time.
+// `head` always points to the segment from which values are popped and `tail` to the segment to
+// which values are pushed. `tail` may lag behind the actual last segment, but never behind `head`.
+#[derive(Debug)]
+pub struct SegQueue<T> {
+    head: CachePadded<Atomic<Segment<T>>>,
+    tail: CachePadded<Atomic<Segment<T>>>,
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    /// One of `EMPTY`, `WRITTEN` and `TAKEN`.
+    state: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+#[derive(Debug)]
+struct Segment<T> {
+    /// The index of the next slot to be claimed by a consumer. May exceed `SEGMENT_CAP`.
+    deq_idx: CachePadded<AtomicUsize>,
+    /// The index of the next slot to be claimed by a producer. May exceed `SEGMENT_CAP`.
+    enq_idx: CachePadded<AtomicUsize>,
+    slots: [Slot<T>; SEGMENT_CAP],
+    next: Atomic<Segment<T>>,
+}
+
+impl<T> Segment<T> {
+    fn new() -> Self {
+        // SAFETY: all-zero is a valid segment: the indices are zero, the slots are `EMPTY` with
+        // uninitialized values, and `next` is null.
+        unsafe { MaybeUninit::zeroed().assume_init() }
+    }
+}
+
+// Any particular `T` should never be accessed concurrently, so no need for `Sync`.
+unsafe impl<T: Send> Sync for SegQueue<T> {}
+unsafe impl<T: Send> Send for SegQueue<T> {}
+
+impl<T> Default for SegQueue<T> {
+    fn default() -> Self {
+        let q = Self {
+            head: CachePadded::new(Atomic::null()),
+            tail: CachePadded::new(Atomic::null()),
+        };
+        unsafe {
+            let guard = &unprotected();
+            let segment = Owned::new(Segment::new()).into_shared(guard);
+            q.head.store(segment, Ordering::Relaxed);
+            q.tail.store(segment, Ordering::Relaxed);
+            q
+        }
+    }
+}
+
+impl<T> SegQueue<T> {
+    /// Create a new, empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `t` to the back of the queue.
+    pub fn push(&self, t: T, guard: &Guard) {
+        let mut t = t;
+        // A segment allocated in a previous iteration that failed to be linked.
+        let mut spare: Option<Owned<Segment<T>>> = None;
+
+        loop {
+            let tail = self.tail.load(Ordering::Acquire, guard);
+            let tail_ref = unsafe { tail.deref() };
+
+            let idx = tail_ref.enq_idx.fetch_add(1, Ordering::SeqCst);
+            if idx < SEGMENT_CAP {
+                let slot = &tail_ref.slots[idx];
+                unsafe { slot.value.get().write(MaybeUninit::new(t)) };
+                if slot
+                    .state
+                    .compare_exchange(EMPTY, WRITTEN, Ordering::Release, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    return;
+                }
+
+                // A consumer has given up on the slot, so take the value back and retry.
+                t = unsafe { slot.value.get().read().assume_init() };
+                continue;
+            }
+
+            // The segment is full.
+            if tail != self.tail.load(Ordering::Acquire, guard) {
+                continue;
+            }
+
+            // If `tail` is not the actual tail, try to "help" by moving the tail pointer forward.
+            let next = tail_ref.next.load(Ordering::Acquire, guard);
+            if !next.is_null() {
+                let _ = self
+                    .tail
+                    .compare_and_set(tail, next, Ordering::Release, guard);
+                continue;
+            }
+
+            // Link a new segment that already contains `t` in its first slot.
+            let new = spare.take().unwrap_or_else(|| Owned::new(Segment::new()));
+            unsafe { new.slots[0].value.get().write(MaybeUninit::new(t)) };
+            new.slots[0].state.store(WRITTEN, Ordering::Relaxed);
+            new.enq_idx.store(1, Ordering::Relaxed);
+
+            match tail_ref
+                .next
+                .compare_and_set(Shared::null(), new, Ordering::Release, guard)
+            {
+                Ok(new) => {
+                    let _ = self
+                        .tail
+                        .compare_and_set(tail, new, Ordering::Release, guard);
+                    return;
+                }
+                Err(e) => {
+                    let new = e.new;
+                    t = unsafe { new.slots[0].value.get().read().assume_init() };
+                    new.slots[0].state.store(EMPTY, Ordering::Relaxed);
+                    new.enq_idx.store(0, Ordering::Relaxed);
+                    spare = Some(new);
+                }
+            }
+        }
+    }
+
+    /// Attempts to dequeue from the front.
+    ///
+    /// Returns `None` if the queue is observed to be empty.
+    pub fn try_pop(&self, guard: &Guard) -> Option<T> {
+        loop {
+            let head = self.head.load(Ordering::Acquire, guard);
+            let head_ref = unsafe { head.deref() };
+
+            // Avoid claiming (and thus poisoning) slots if the queue is empty.
+            if head_ref.deq_idx.load(Ordering::SeqCst) >= head_ref.enq_idx.load(Ordering::SeqCst)
+                && head_ref.next.load(Ordering::Acquire, guard).is_null()
+            {
+                return None;
+            }
+
+            let idx = head_ref.deq_idx.fetch_add(1, Ordering::SeqCst);
+            if idx < SEGMENT_CAP {
+                let slot = &head_ref.slots[idx];
+                if slot.state.swap(TAKEN, Ordering::Acquire) == WRITTEN {
+                    return Some(unsafe { slot.value.get().read().assume_init() });
+                }
+
+                // The producer of this slot is late, and will retry with another slot.
+                continue;
+            }
+
+            // The segment is exhausted, so move on to the next segment.
+            let next = head_ref.next.load(Ordering::Acquire, guard);
+            if next.is_null() {
+                return None;
+            }
+
+            // Moves `tail` if it's stale, so that `tail` never points to a retired segment.
+            let tail = self.tail.load(Ordering::Relaxed, guard);
+            if tail == head {
+                let _ = self
+                    .tail
+                    .compare_and_set(tail, next, Ordering::Release, guard);
+            }
+
+            if self
+                .head
+                .compare_and_set(head, next, Ordering::Release, guard)
+                .is_ok()
+            {
+                // Every slot of the segment has been claimed by a consumer, so it no longer
+                // contains a value that is not taken.
+                unsafe { guard.defer_destroy(head) };
+            }
+        }
+    }
+}
+
+impl<T> Drop for SegQueue<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+
+            let mut segment = self.head.load(Ordering::Relaxed, guard);
+            while !segment.is_null() {
+                let segment_ref = segment.deref();
+                if mem::needs_drop::<T>() {
+                    for slot in segment_ref.slots.iter() {
+                        if slot.state.load(Ordering::Relaxed) == WRITTEN {
+                            drop(slot.value.get().read().assume_init());
+                        }
+                    }
+                }
+                let next = segment_ref.next.load(Ordering::Relaxed, guard);
+                drop(segment.into_owned());
+                segment = next;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_epoch::pin;
+    use crossbeam_utils::thread;
+
+    const CONC_COUNT: i64 = 1000000;
+
+    #[test]
+    fn push_try_pop_seq() {
+        let q = SegQueue::new();
+        let guard = &pin();
+        assert_eq!(q.try_pop(guard), None);
+        for i in 0..(3 * SEGMENT_CAP as i64 + 7) {
+            q.push(i, guard);
+        }
+        for i in 0..(3 * SEGMENT_CAP as i64 + 7) {
+            assert_eq!(q.try_pop(guard), Some(i));
+        }
+        assert_eq!(q.try_pop(guard), None);
+    }
+
+    #[test]
+    fn push_try_pop_many_spsc() {
+        let q: SegQueue<i64> = SegQueue::new();
+
+        thread::scope(|scope| {
+            scope.spawn(|_| {
+                let mut next = 0;
+
+                while next < CONC_COUNT {
+                    if let Some(elem) = q.try_pop(&pin()) {
+                        assert_eq!(elem, next);
+                        next += 1;
+                    }
+                }
+            });
+
+            for i in 0..CONC_COUNT {
+                q.push(i, &pin())
+            }
+        })
+        .unwrap();
+        assert_eq!(q.try_pop(&pin()), None);
+    }
+
+    #[test]
+    fn push_try_pop_many_mpmc() {
+        const THREADS: i64 = 4;
+        const COUNT: i64 = CONC_COUNT / THREADS;
+
+        let q: SegQueue<i64> = SegQueue::new();
+        let popped = thread::scope(|scope| {
+            for t in 0..THREADS {
+                let q = &q;
+                scope.spawn(move |_| {
+                    for i in 0..COUNT {
+                        q.push(t * COUNT + i, &pin());
+                    }
+                });
+            }
+
+            let mut handles = Vec::new();
+            for _ in 0..THREADS {
+                handles.push(scope.spawn(|_| {
+                    let mut last = vec![-1; THREADS as usize];
+                    let mut popped = Vec::new();
+                    for _ in 0..COUNT {
+                        if let Some(elem) = q.try_pop(&pin()) {
+                            // Values from the same producer are popped in order.
+                            let producer = (elem / COUNT) as usize;
+                            assert!(elem > last[producer]);
+                            last[producer] = elem;
+                            popped.push(elem);
+                        }
+                    }
+                    popped
+                }));
+            }
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+
+        let mut all = popped;
+        while let Some(elem) = q.try_pop(&pin()) {
+            all.push(elem);
+        }
+        all.sort_unstable();
+        assert_eq!(all, (0..THREADS * COUNT).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn drop_remaining() {
+        struct Canary<'a>(&'a AtomicUsize);
+
+        impl Drop for Canary<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let dropped = AtomicUsize::new(0);
+        {
+            let q = SegQueue::new();
+            let guard = &pin();
+            for _ in 0..(2 * SEGMENT_CAP + 1) {
+                q.push(Canary(&dropped), guard);
+            }
+            for _ in 0..(SEGMENT_CAP + 3) {
+                drop(q.try_pop(guard));
+            }
+            assert_eq!(dropped.load(Ordering::Relaxed), SEGMENT_CAP + 3);
+        }
+        assert_eq!(dropped.load(Ordering::Relaxed), 2 * SEGMENT_CAP + 1);
+    }
+}
@@ -0,0 +1,371 @@
+//! Bounded single-producer single-consumer ring buffer.
+//!
+//! The buffer is split into a [`Producer`] and a [`Consumer`], each of which is owned by a single
+//! thread, so every operation finishes without retrying.
+//!
+//! # Example
+//!
+//! ```
+//! let (mut tx, mut rx) = lockfree::spsc::channel(4);
+//! assert_eq!(tx.push_slice(&[1, 2, 3, 4, 5]), 4);
+//! assert_eq!(tx.push(5), Err(5));
+//!
+//! let mut buf = [0; 3];
+//! assert_eq!(rx.pop_into(&mut buf), 3);
+//! assert_eq!(buf, [1, 2, 3]);
+//! assert_eq!(rx.pop(), Some(4));
+//! assert_eq!(rx.pop(), None);
+//! ```
+
+use core::cell::UnsafeCell;
+use core::cmp;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crossbeam_utils::CachePadded;
+
+/// Creates a ring buffer that holds at most `capacity` values, and returns its two ends.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    assert!(capacity > 0, "capacity must be positive");
+    let buffer = Arc::new(Buffer {
+        head: CachePadded::new(AtomicUsize::new(0)),
+        tail: CachePadded::new(AtomicUsize::new(0)),
+        slots: (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+    });
+    let producer = Producer {
+        buffer: buffer.clone(),
+        tail: 0,
+        head_cache: 0,
+    };
+    let consumer = Consumer {
+        buffer,
+        head: 0,
+        tail_cache: 0,
+    };
+    (producer, consumer)
+}
+
+// Positions range over `0..2 * capacity`, so that a full buffer (`tail - head == capacity`) is
+// distinguished from an empty one (`tail == head`). The slot of position `pos` is `pos % capacity`.
+#[derive(Debug)]
+struct Buffer<T> {
+    /// The position of the next value to be popped. Written only by the consumer.
+    head: CachePadded<AtomicUsize>,
+    /// The position of the next value to be pushed. Written only by the producer.
+    tail: CachePadded<AtomicUsize>,
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+impl<T> Buffer<T> {
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the number of values between the positions `head` and `tail`.
+    fn distance(&self, head: usize, tail: usize) -> usize {
+        if tail >= head {
+            tail - head
+        } else {
+            tail + 2 * self.capacity() - head
+        }
+    }
+
+    /// Advances the position `pos` by `n`.
+    fn advance(&self, pos: usize, n: usize) -> usize {
+        let pos = pos + n;
+        if pos >= 2 * self.capacity() {
+            pos - 2 * self.capacity()
+        } else {
+            pos
+        }
+    }
+
+    fn index(&self, pos: usize) -> usize {
+        if pos >= self.capacity() {
+            pos - self.capacity()
+        } else {
+            pos
+        }
+    }
+
+    fn slot(&self, pos: usize) -> *mut T {
+        self.slots[self.index(pos)].get() as *mut T
+    }
+
+    /// Returns the contiguous slots from position `pos` of length at most `n`.
+    fn contiguous(&self, pos: usize, n: usize) -> (*mut T, usize) {
+        (
+            self.slot(pos),
+            cmp::min(n, self.capacity() - self.index(pos)),
+        )
+    }
+}
+
+impl<T> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        let mut head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        while head != tail {
+            unsafe { ptr::drop_in_place(self.slot(head)) };
+            head = self.advance(head, 1);
+        }
+    }
+}
+
+/// The producing end of a ring buffer.
+#[derive(Debug)]
+pub struct Producer<T> {
+    buffer: Arc<Buffer<T>>,
+    /// The local copy of `buffer.tail`.
+    tail: usize,
+    /// A stale value of `buffer.head`, which is refreshed only when the buffer looks full.
+    head_cache: usize,
+}
+
+/// The consuming end of a ring buffer.
+#[derive(Debug)]
+pub struct Consumer<T> {
+    buffer: Arc<Buffer<T>>,
+    /// The local copy of `buffer.head`.
+    head: usize,
+    /// A stale value of `buffer.tail`, which is refreshed only when the buffer looks empty.
+    tail_cache: usize,
+}
+
+// Each end is used by one thread at a time, and the values are moved between the threads.
+unsafe impl<T: Send> Send for Producer<T> {}
+unsafe impl<T: Send> Send for Consumer<T> {}
+
+impl<T> Producer<T> {
+    /// Returns the maximum number of values in the buffer.
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// Returns the number of free slots, refreshing the cached head if there are less than `n`.
+    fn free(&mut self, n: usize) -> usize {
+        let free = self.capacity() - self.buffer.distance(self.head_cache, self.tail);
+        if free >= n {
+            return free;
+        }
+        self.head_cache = self.buffer.head.load(Ordering::Acquire);
+        self.capacity() - self.buffer.distance(self.head_cache, self.tail)
+    }
+
+    /// Returns `true` if the buffer is observed to be full.
+    pub fn is_full(&mut self) -> bool {
+        self.free(1) == 0
+    }
+
+    /// Pushes `t` to the buffer.
+    ///
+    /// Returns `Err(t)` if the buffer is full.
+    pub fn push(&mut self, t: T) -> Result<(), T> {
+        if self.free(1) == 0 {
+            return Err(t);
+        }
+        unsafe { self.buffer.slot(self.tail).write(t) };
+        self.tail = self.buffer.advance(self.tail, 1);
+        self.buffer.tail.store(self.tail, Ordering::Release);
+        Ok(())
+    }
+
+    /// Pushes as many values from the front of `values` as fit in the buffer, and returns their
+    /// number.
+    pub fn push_slice(&mut self, values: &[T]) -> usize
+    where
+        T: Copy,
+    {
+        let n = cmp::min(values.len(), self.free(values.len()));
+        let mut pushed = 0;
+        while pushed < n {
+            // At most twice, as the free slots may wrap around.
+            let (dst, len) = self.buffer.contiguous(self.tail, n - pushed);
+            unsafe { ptr::copy_nonoverlapping(values[pushed..].as_ptr(), dst, len) };
+            pushed += len;
+            self.tail = self.buffer.advance(self.tail, len);
+        }
+        self.buffer.tail.store(self.tail, Ordering::Release);
+        n
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Returns the maximum number of values in the buffer.
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// Returns the number of values, refreshing the cached tail if there are less than `n`.
+    fn available(&mut self, n: usize) -> usize {
+        let available = self.buffer.distance(self.head, self.tail_cache);
+        if available >= n {
+            return available;
+        }
+        self.tail_cache = self.buffer.tail.load(Ordering::Acquire);
+        self.buffer.distance(self.head, self.tail_cache)
+    }
+
+    /// Returns `true` if the buffer is observed to be empty.
+    pub fn is_empty(&mut self) -> bool {
+        self.available(1) == 0
+    }
+
+    /// Pops the oldest value from the buffer.
+    ///
+    /// Returns `None` if the buffer is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.available(1) == 0 {
+            return None;
+        }
+        let t = unsafe { self.buffer.slot(self.head).read() };
+        self.head = self.buffer.advance(self.head, 1);
+        self.buffer.head.store(self.head, Ordering::Release);
+        Some(t)
+    }
+
+    /// Pops as many values as fit in `buf` into its front, and returns their number.
+    pub fn pop_into(&mut self, buf: &mut [T]) -> usize
+    where
+        T: Copy,
+    {
+        let n = cmp::min(buf.len(), self.available(buf.len()));
+        let mut popped = 0;
+        while popped < n {
+            // At most twice, as the values may wrap around.
+            let (src, len) = self.buffer.contiguous(self.head, n - popped);
+            unsafe { ptr::copy_nonoverlapping(src, buf[popped..].as_mut_ptr(), len) };
+            popped += len;
+            self.head = self.buffer.advance(self.head, len);
+        }
+        self.buffer.head.store(self.head, Ordering::Release);
+        n
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_utils::thread::scope;
+    use std::thread;
+
+    #[test]
+    fn push_pop_seq() {
+        let (mut tx, mut rx) = channel(3);
+        assert!(rx.is_empty());
+        for round in 0..10 {
+            for i in 0..3 {
+                assert_eq!(tx.push(round * 3 + i), Ok(()));
+            }
+            assert!(tx.is_full());
+            assert_eq!(tx.push(-1), Err(-1));
+            for i in 0..3 {
+                assert_eq!(rx.pop(), Some(round * 3 + i));
+            }
+            assert_eq!(rx.pop(), None);
+        }
+    }
+
+    #[test]
+    fn slices_wrap_around() {
+        let (mut tx, mut rx) = channel(5);
+        let mut buf = [0; 4];
+        let mut next_push = 0;
+        let mut next_pop = 0;
+        for _ in 0..100 {
+            let values = [next_push, next_push + 1, next_push + 2];
+            next_push += tx.push_slice(&values) as i32;
+
+            let n = rx.pop_into(&mut buf[..2]);
+            for &v in &buf[..n] {
+                assert_eq!(v, next_pop);
+                next_pop += 1;
+            }
+        }
+        let n = rx.pop_into(&mut buf);
+        for &v in &buf[..n] {
+            assert_eq!(v, next_pop);
+            next_pop += 1;
+        }
+        assert_eq!(next_pop, next_push);
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    fn spsc() {
+        const COUNT: usize = 100_000;
+
+        let (mut tx, mut rx) = channel(64);
+        scope(|s| {
+            s.spawn(move |_| {
+                let mut i = 0;
+                while i < COUNT {
+                    let pushed = if i % 2 == 0 {
+                        tx.push(i).is_ok() as usize
+                    } else {
+                        let end = cmp::min(i + 7, COUNT);
+                        tx.push_slice(&(i..end).collect::<Vec<_>>())
+                    };
+                    if pushed == 0 {
+                        thread::yield_now();
+                    }
+                    i += pushed;
+                }
+            });
+
+            let mut next = 0;
+            let mut buf = [0; 5];
+            while next < COUNT {
+                let n = rx.pop_into(&mut buf);
+                for &v in &buf[..n] {
+                    assert_eq!(v, next);
+                    next += 1;
+                }
+                match rx.pop() {
+                    Some(v) => {
+                        assert_eq!(v, next);
+                        next += 1;
+                    }
+                    None if n == 0 => thread::yield_now(),
+                    None => {}
+                }
+            }
+            assert_eq!(rx.pop(), None);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn drop_remaining() {
+        struct Canary<'a>(&'a AtomicUsize);
+
+        impl Drop for Canary<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let dropped = AtomicUsize::new(0);
+        {
+            let (mut tx, mut rx) = channel(4);
+            for _ in 0..6 {
+                assert!(tx.push(Canary(&dropped)).is_ok());
+                drop(rx.pop());
+            }
+            for _ in 0..3 {
+                assert!(tx.push(Canary(&dropped)).is_ok());
+            }
+            drop(tx);
+            assert_eq!(dropped.load(Ordering::Relaxed), 6);
+        }
+        assert_eq!(dropped.load(Ordering::Relaxed), 9);
+    }
+}
@@ -1,56 +1,130 @@
+//! Treiber's lock-free stack.
+
+use core::marker::PhantomData;
 use core::mem::ManuallyDrop;
 use core::ptr;
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use crossbeam_epoch::Guard;
 
-use crossbeam_epoch::{Atomic, Owned};
+use crate::pool;
+use crate::reclaim::{Epoch, Reclaim};
 
 /// Treiber's lock-free stack.
 ///
-/// Usable with any number of producers and consumers.
+/// Usable with any number of producers and consumers. Popped nodes are reclaimed by the scheme
+/// `R`.
 #[derive(Debug)]
-pub struct Stack<T> {
-    head: Atomic<Node<T>>,
+pub struct Stack<T, R: Reclaim = Epoch> {
+    head: AtomicPtr<Node<T>>,
+    /// The maximum number of nodes in each thread's pool. Zero if pooling is disabled.
+    pool_cap: usize,
+    _marker: PhantomData<R>,
 }
 
 #[derive(Debug)]
 struct Node<T> {
     data: ManuallyDrop<T>,
-    next: Atomic<Node<T>>,
+    next: AtomicPtr<Node<T>>,
 }
 
-impl<T> Default for Stack<T> {
+unsafe impl<T: Send, R: Reclaim> Sync for Stack<T, R> {}
+unsafe impl<T: Send, R: Reclaim> Send for Stack<T, R> {}
+
+impl<T, R: Reclaim> Default for Stack<T, R> {
     fn default() -> Self {
         Self {
-            head: Atomic::null(),
+            head: AtomicPtr::new(ptr::null_mut()),
+            pool_cap: 0,
+            _marker: PhantomData,
         }
     }
 }
 
 impl<T> Stack<T> {
-    /// Creates a new, empty stack.
-    pub fn new() -> Stack<T> {
+    /// Creates a new, empty stack reclaimed by [`Epoch`].
+    ///
+    /// Use `Default` for the other reclamation schemes.
+    pub fn new() -> Self {
         Self::default()
     }
 
+    /// Creates a new, empty stack that recycles nodes.
+    ///
+    /// Once reclaimed, the popped nodes are put back to the per-thread node pool, which holds at
+    /// most `pool_cap` nodes, instead of being freed, and pushes take nodes from the pool.
+    pub fn with_pool(pool_cap: usize) -> Self {
+        let mut stack = Self::default();
+        stack.pool_cap = pool_cap;
+        stack
+    }
+
+    /// Returns an iterator over the elements from top to bottom, without popping them.
+    ///
+    /// The iterator walks the nodes that are reachable when it visits them, so it may miss
+    /// concurrently pushed elements and yield concurrently popped ones. As a concurrent `pop` moves
+    /// the value out of its node, the values are copied rather than borrowed.
+    pub fn iter<'g>(&self, guard: &'g Guard) -> Iter<'g, T>
+    where
+        T: Copy,
+    {
+        Iter {
+            node: self.head.load(Ordering::Acquire),
+            _guard: guard,
+        }
+    }
+}
+
+impl<T, R: Reclaim> Stack<T, R> {
+    /// Allocates a node, from the pool if enabled.
+    fn alloc_node(&self, t: T, next: *mut Node<T>) -> *mut Node<T> {
+        let n = pool::alloc::<Node<T>>(self.pool_cap);
+        unsafe {
+            n.write(Node {
+                data: ManuallyDrop::new(t),
+                next: AtomicPtr::new(next),
+            })
+        };
+        n
+    }
+
     /// Pushes a value on top of the stack.
     pub fn push(&self, t: T) {
-        let mut n = Owned::new(Node {
-            data: ManuallyDrop::new(t),
-            next: Atomic::null(),
-        });
+        let n = self.alloc_node(t, ptr::null_mut());
+        self.push_chain(n, n);
+    }
 
-        let guard = crossbeam_epoch::pin();
+    /// Pushes all values of `iter` on top of the stack, the last one ending up on the top.
+    ///
+    /// The values are first linked into a private chain, which is then published with a single
+    /// CAS. Hence the values are pushed atomically: no other operation observes a prefix of them.
+    pub fn push_all<I: IntoIterator<Item = T>>(&self, iter: I) {
+        let mut iter = iter.into_iter();
+        let first = some_or!(iter.next(), return);
 
+        // The chain is private until it is published, so relaxed accesses suffice.
+        let bottom = self.alloc_node(first, ptr::null_mut());
+        let mut top = bottom;
+        for t in iter {
+            top = self.alloc_node(t, top);
+        }
+        self.push_chain(top, bottom);
+    }
+
+    /// Publishes the private chain from `top` to `bottom` on top of the stack.
+    fn push_chain(&self, top: *mut Node<T>, bottom: *mut Node<T>) {
+        // Pushing doesn't dereference shared nodes, so it needs no protection.
+        let bottom_ref = unsafe { &*bottom };
+        let mut head = self.head.load(Ordering::Relaxed);
         loop {
-            let head = self.head.load(Ordering::Relaxed, &guard);
-            n.next.store(head, Ordering::Relaxed);
+            bottom_ref.next.store(head, Ordering::Relaxed);
 
             match self
                 .head
-                .compare_and_set(head, n, Ordering::Release, &guard)
+                .compare_exchange(head, top, Ordering::Release, Ordering::Relaxed)
             {
                 Ok(_) => break,
-                Err(e) => n = e.new,
+                Err(h) => head = h,
             }
         }
     }
@@ -59,21 +133,22 @@ impl<T> Stack<T> {
     ///
     /// Returns `None` if the stack is empty.
     pub fn pop(&self) -> Option<T> {
-        let guard = crossbeam_epoch::pin();
+        let guard = R::pin();
+        let mut shield = R::Shield::default();
         loop {
-            let head = self.head.load(Ordering::Acquire, &guard);
+            let head = R::protect(&self.head, &mut shield, &guard);
 
             match unsafe { head.as_ref() } {
                 Some(h) => {
-                    let next = h.next.load(Ordering::Relaxed, &guard);
+                    let next = h.next.load(Ordering::Relaxed);
 
                     if self
                         .head
-                        .compare_and_set(head, next, Ordering::Relaxed, &guard)
+                        .compare_exchange(head, next, Ordering::Relaxed, Ordering::Relaxed)
                         .is_ok()
                     {
                         unsafe {
-                            guard.defer_destroy(head);
+                            R::retire_to_pool(head, self.pool_cap, &guard);
                             return Some(ManuallyDrop::into_inner(ptr::read(&(*h).data)));
                         }
                     }
@@ -83,22 +158,140 @@ impl<T> Stack<T> {
         }
     }
 
+    /// Pops all elements from the stack at once.
+    ///
+    /// The stack is emptied with a single swap of its head, and the returned iterator yields the
+    /// popped elements from top to bottom.
+    pub fn pop_all(&self) -> PopAll<T, R> {
+        PopAll {
+            head: self.head.swap(ptr::null_mut(), Ordering::Acquire),
+            pool_cap: self.pool_cap,
+            _marker: PhantomData,
+        }
+    }
+
     /// Returns `true` if the stack is empty.
     pub fn is_empty(&self) -> bool {
-        let guard = crossbeam_epoch::pin();
-        self.head.load(Ordering::Acquire, &guard).is_null()
+        self.head.load(Ordering::Acquire).is_null()
+    }
+
+    /// Returns an iterator that pops elements until the stack is observed to be empty.
+    pub fn drain(&self) -> Drain<'_, T, R> {
+        Drain { stack: self }
+    }
+}
+
+/// An iterator over the elements of a [`Stack`], created by [`Stack::iter`].
+#[derive(Debug)]
+pub struct Iter<'g, T> {
+    node: *const Node<T>,
+    /// The nodes are not freed while the guard is alive.
+    _guard: &'g Guard,
+}
+
+impl<T: Copy> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let node = unsafe { self.node.as_ref()? };
+        self.node = node.next.load(Ordering::Acquire);
+        Some(*node.data)
+    }
+}
+
+/// A draining iterator of a [`Stack`], created by [`Stack::drain`].
+#[derive(Debug)]
+pub struct Drain<'s, T, R: Reclaim = Epoch> {
+    stack: &'s Stack<T, R>,
+}
+
+impl<T, R: Reclaim> Iterator for Drain<'_, T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.stack.pop()
+    }
+}
+
+/// An owning iterator of a [`Stack`], which yields the elements from top to bottom.
+#[derive(Debug)]
+pub struct IntoIter<T, R: Reclaim = Epoch> {
+    stack: Stack<T, R>,
+}
+
+impl<T, R: Reclaim> Iterator for IntoIter<T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // The stack is owned, so the nodes are freed right away. The rest is freed by `Stack::drop`.
+        let head = self.stack.head.get_mut();
+        if head.is_null() {
+            return None;
+        }
+        let mut node = unsafe { Box::from_raw(*head) };
+        *head = *node.next.get_mut();
+        Some(unsafe { ManuallyDrop::take(&mut node.data) })
+    }
+}
+
+impl<T, R: Reclaim> IntoIterator for Stack<T, R> {
+    type Item = T;
+    type IntoIter = IntoIter<T, R>;
+
+    fn into_iter(self) -> IntoIter<T, R> {
+        IntoIter { stack: self }
     }
 }
 
-impl<T> Drop for Stack<T> {
+/// An owning iterator over the elements taken from a [`Stack`] by [`Stack::pop_all`].
+#[derive(Debug)]
+pub struct PopAll<T, R: Reclaim = Epoch> {
+    /// The remaining chain. It is unreachable from the stack, but the nodes may still be read by
+    /// the concurrent `pop`s that loaded them before the swap, so they are retired.
+    head: *mut Node<T>,
+    pool_cap: usize,
+    _marker: PhantomData<R>,
+}
+
+unsafe impl<T: Send, R: Reclaim> Send for PopAll<T, R> {}
+
+impl<T, R: Reclaim> Iterator for PopAll<T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let head = self.head;
+        let h = unsafe { head.as_ref()? };
+        self.head = h.next.load(Ordering::Relaxed);
+        unsafe {
+            let data = ManuallyDrop::into_inner(ptr::read(&h.data));
+            R::retire_to_pool(head, self.pool_cap, &R::pin());
+            Some(data)
+        }
+    }
+}
+
+impl<T, R: Reclaim> Drop for PopAll<T, R> {
+    fn drop(&mut self) {
+        for _ in self {}
+    }
+}
+
+impl<T, R: Reclaim> Drop for Stack<T, R> {
     fn drop(&mut self) {
-        while self.pop().is_some() {}
+        // No other thread can access the nodes, so they are freed right away.
+        let mut node = *self.head.get_mut();
+        while !node.is_null() {
+            let mut n = unsafe { Box::from_raw(node) };
+            unsafe { ManuallyDrop::drop(&mut n.data) };
+            node = *n.next.get_mut();
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use core::sync::atomic::AtomicUsize;
     use crossbeam_utils::thread::scope;
 
     #[test]
@@ -119,4 +312,116 @@ mod test {
 
         assert!(stack.pop().is_none());
     }
+
+    #[test]
+    fn push_all_pop_all() {
+        let stack = Stack::new();
+        stack.push(0);
+        stack.push_all(1..4);
+        stack.push_all(Vec::new());
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop_all().collect::<Vec<_>>(), vec![2, 1, 0]);
+        assert!(stack.is_empty());
+        assert_eq!(stack.pop_all().next(), None);
+    }
+
+    #[test]
+    fn push_all_pop_all_concurrent() {
+        const THREADS: usize = 8;
+        const ITER: usize = 1_000;
+        const BATCH: usize = 16;
+
+        let stack = Stack::new();
+        let popped = scope(|scope| {
+            let mut handles = Vec::new();
+            for t in 0..THREADS {
+                let stack = &stack;
+                handles.push(scope.spawn(move |_| {
+                    let mut popped = Vec::new();
+                    for i in 0..ITER {
+                        let base = (t * ITER + i) * BATCH;
+                        stack.push_all(base..base + BATCH);
+                        if i % 2 == 0 {
+                            popped.extend(stack.pop());
+                        } else {
+                            popped.extend(stack.pop_all());
+                        }
+                    }
+                    popped
+                }));
+            }
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+
+        let mut all = popped;
+        all.extend(stack.pop_all());
+        all.sort_unstable();
+        assert_eq!(all, (0..THREADS * ITER * BATCH).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn iterators() {
+        let stack = Stack::new();
+        stack.push_all(0..10);
+        assert_eq!(
+            stack.iter(&crossbeam_epoch::pin()).collect::<Vec<_>>(),
+            (0..10).rev().collect::<Vec<_>>()
+        );
+        assert_eq!(stack.drain().take(3).collect::<Vec<_>>(), vec![9, 8, 7]);
+        assert_eq!(stack.drain().count(), 7);
+        assert!(stack.is_empty());
+
+        stack.push_all(0..5);
+        assert_eq!(stack.into_iter().collect::<Vec<_>>(), vec![4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn iterators_drop_once() {
+        struct Canary<'a>(&'a AtomicUsize);
+
+        impl Drop for Canary<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let dropped = AtomicUsize::new(0);
+        {
+            let stack = Stack::new();
+            stack.push_all((0..10).map(|_| Canary(&dropped)));
+            stack.drain().take(3).for_each(drop);
+            assert_eq!(dropped.load(Ordering::Relaxed), 3);
+
+            let mut iter = stack.into_iter();
+            drop(iter.next());
+            assert_eq!(dropped.load(Ordering::Relaxed), 4);
+        }
+        assert_eq!(dropped.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn push_pool() {
+        let stack = Stack::with_pool(64);
+
+        scope(|scope| {
+            for _ in 0..10 {
+                scope.spawn(|_| {
+                    for i in 0..10_000 {
+                        stack.push(i);
+                        stack.push_all(0..3);
+                        for _ in 0..4 {
+                            assert!(stack.pop().is_some());
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert!(stack.pop().is_none());
+    }
 }
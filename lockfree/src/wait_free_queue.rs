@@ -0,0 +1,563 @@
+//! Kogan-Petrank wait-free queue.
+//!
+//! Usable with any number of producers and consumers, as long as at most `MAX_THREADS` threads use
+//! the queue at the same time.
+//!
+//! Kogan and Petrank.  Wait-Free Queues With Multiple Enqueuers and Dequeuers.  PPoPP 2011.
+//! https://dl.acm.org/doi/10.1145/1941553.1941585
+
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+use crossbeam_utils::CachePadded;
+
+/// The maximum number of threads that can use wait-free queues at the same time.
+const MAX_THREADS: usize = 128;
+
+/// `deq_tid` of a node that is not reserved by a dequeuer.
+const NO_TID: usize = usize::MAX;
+
+/// Registry of the thread ids, which are the indices into the announcement arrays.
+///
+/// A thread id is allocated on the first operation of a thread and released when the thread
+/// exits, so that it can be reused by a new thread.
+mod registry {
+    use super::MAX_THREADS;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    const BITS: usize = 0usize.count_zeros() as usize;
+
+    /// The number of words in the bitmap.
+    const WORDS: usize = MAX_THREADS / BITS;
+
+    // Only used to initialize `ALLOCATED`.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+    /// Bitmap of the allocated thread ids.
+    static ALLOCATED: [AtomicUsize; WORDS] = [ZERO; WORDS];
+
+    /// One plus the largest thread id ever allocated.
+    static HIGH_WATER: AtomicUsize = AtomicUsize::new(0);
+
+    struct Registration(usize);
+
+    impl Drop for Registration {
+        fn drop(&mut self) {
+            let (word, bit) = (self.0 / BITS, self.0 % BITS);
+            let _ = ALLOCATED[word].fetch_and(!(1 << bit), Ordering::Release);
+        }
+    }
+
+    fn register() -> Registration {
+        for (word, bitmap) in ALLOCATED.iter().enumerate() {
+            let mut current = bitmap.load(Ordering::Relaxed);
+            while current != !0 {
+                let bit = (!current).trailing_zeros() as usize;
+                match bitmap.compare_exchange(
+                    current,
+                    current | (1 << bit),
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let tid = word * BITS + bit;
+                        let _ = HIGH_WATER.fetch_max(tid + 1, Ordering::Release);
+                        return Registration(tid);
+                    }
+                    Err(c) => current = c,
+                }
+            }
+        }
+        panic!("more than {} threads use wait-free queues", MAX_THREADS);
+    }
+
+    thread_local! {
+        static REGISTRATION: Registration = register();
+    }
+
+    /// Returns the id of the current thread.
+    pub(super) fn current() -> usize {
+        REGISTRATION.with(|r| r.0)
+    }
+
+    /// Returns an upper bound of the allocated thread ids.
+    pub(super) fn high_water() -> usize {
+        HIGH_WATER.load(Ordering::Acquire)
+    }
+}
+
+/// Kogan-Petrank queue.
+///
+/// Has the same interface as [`Queue`](crate::Queue), but each operation finishes in a bounded
+/// number of steps regardless of the other threads.
+// The representation is that of the Michael-Scott queue. In addition, each thread announces its
+// operation with a phase number in `state`, and before performing its own operation, a thread
+// helps all pending operations with smaller or equal phases. Therefore an operation finishes at
+// the latest when all threads have helped it.
+#[derive(Debug)]
+pub struct WaitFreeQueue<T> {
+    head: CachePadded<Atomic<Node<T>>>,
+    tail: CachePadded<Atomic<Node<T>>>,
+    /// The announcement array, indexed by thread ids.
+    state: Box<[CachePadded<Atomic<OpDesc<T>>>]>,
+    /// The source of phase numbers.
+    phase: CachePadded<AtomicUsize>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    /// Uninitialized for the sentinel node. See `Queue`.
+    data: MaybeUninit<T>,
+    next: Atomic<Node<T>>,
+    /// The thread that enqueued this node.
+    enq_tid: usize,
+    /// The thread that dequeues the value in the next node, or `NO_TID`.
+    deq_tid: AtomicUsize,
+}
+
+/// Descriptor of an operation, immutable once announced.
+#[derive(Debug)]
+struct OpDesc<T> {
+    phase: usize,
+    pending: bool,
+    enqueue: bool,
+    /// For an enqueue, the node to be enqueued. For a dequeue, the sentinel node whose next node's
+    /// value is dequeued, or null if the queue is empty.
+    node: *const Node<T>,
+}
+
+// Any particular `T` should never be accessed concurrently, so no need for `Sync`.
+unsafe impl<T: Send> Sync for WaitFreeQueue<T> {}
+unsafe impl<T: Send> Send for WaitFreeQueue<T> {}
+
+impl<T> Default for WaitFreeQueue<T> {
+    fn default() -> Self {
+        let q = Self {
+            head: CachePadded::new(Atomic::null()),
+            tail: CachePadded::new(Atomic::null()),
+            state: (0..MAX_THREADS)
+                .map(|_| CachePadded::new(Atomic::null()))
+                .collect(),
+            phase: CachePadded::new(AtomicUsize::new(0)),
+        };
+        let sentinel = Owned::new(Node {
+            data: MaybeUninit::uninit(),
+            next: Atomic::null(),
+            enq_tid: NO_TID,
+            deq_tid: AtomicUsize::new(NO_TID),
+        });
+        unsafe {
+            let guard = &unprotected();
+            let sentinel = sentinel.into_shared(guard);
+            q.head.store(sentinel, Ordering::Relaxed);
+            q.tail.store(sentinel, Ordering::Relaxed);
+            q
+        }
+    }
+}
+
+impl<T> WaitFreeQueue<T> {
+    /// Create a new, empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `t` to the back of the queue.
+    pub fn push(&self, t: T, guard: &Guard) {
+        let phase = self.announce_push(t, guard);
+        self.help(phase, guard);
+        self.help_finish_enq(guard);
+    }
+
+    /// Attempts to dequeue from the front.
+    ///
+    /// Returns `None` if the queue is observed to be empty.
+    pub fn try_pop(&self, guard: &Guard) -> Option<T> {
+        let tid = registry::current();
+        let phase = self.announce(tid, false, ptr::null(), guard);
+        self.help(phase, guard);
+        self.help_finish_deq(guard);
+
+        // The operation is finished, so the descriptor is no longer changed by the other threads.
+        let desc = unsafe { self.state[tid].load(Ordering::Acquire, guard).deref() };
+        let node = unsafe { Shared::from(desc.node).as_ref()? };
+        let next = node.next.load(Ordering::Acquire, guard);
+        Some(unsafe { ptr::read(next.deref().data.as_ptr()) })
+    }
+
+    /// Announces a push of `t` by the current thread, and returns its phase.
+    fn announce_push(&self, t: T, guard: &Guard) -> usize {
+        let tid = registry::current();
+        let node = Owned::new(Node {
+            data: MaybeUninit::new(t),
+            next: Atomic::null(),
+            enq_tid: tid,
+            deq_tid: AtomicUsize::new(NO_TID),
+        })
+        .into_shared(guard);
+        self.announce(tid, true, node.as_raw(), guard)
+    }
+
+    /// Announces an operation of the given thread with a new phase, and returns the phase.
+    fn announce(&self, tid: usize, enqueue: bool, node: *const Node<T>, guard: &Guard) -> usize {
+        let phase = self.phase.fetch_add(1, Ordering::Relaxed);
+        let desc = Owned::new(OpDesc {
+            phase,
+            pending: true,
+            enqueue,
+            node,
+        });
+        let old = self.state[tid].swap(desc, Ordering::AcqRel, guard);
+        if !old.is_null() {
+            unsafe { guard.defer_destroy(old) };
+        }
+        phase
+    }
+
+    /// Replaces the descriptor `cur` of the given thread with a new descriptor.
+    fn update<'g>(
+        &self,
+        tid: usize,
+        cur: Shared<'g, OpDesc<T>>,
+        pending: bool,
+        node: *const Node<T>,
+        guard: &'g Guard,
+    ) -> bool {
+        let cur_ref = unsafe { cur.deref() };
+        let new = Owned::new(OpDesc {
+            phase: cur_ref.phase,
+            pending,
+            enqueue: cur_ref.enqueue,
+            node,
+        });
+        if self.state[tid]
+            .compare_and_set(cur, new, Ordering::AcqRel, guard)
+            .is_ok()
+        {
+            unsafe { guard.defer_destroy(cur) };
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns `true` if `desc` is a pending operation with a phase not larger than `phase`.
+    fn is_pending(desc: Shared<'_, OpDesc<T>>, phase: usize) -> bool {
+        match unsafe { desc.as_ref() } {
+            Some(desc) => desc.pending && desc.phase <= phase,
+            None => false,
+        }
+    }
+
+    /// Helps all pending operations with phases not larger than `phase`.
+    fn help(&self, phase: usize, guard: &Guard) {
+        for tid in 0..registry::high_water() {
+            let desc = self.state[tid].load(Ordering::Acquire, guard);
+            if Self::is_pending(desc, phase) {
+                if unsafe { desc.deref() }.enqueue {
+                    self.help_enq(tid, phase, guard);
+                } else {
+                    self.help_deq(tid, phase, guard);
+                }
+            }
+        }
+    }
+
+    fn help_enq(&self, tid: usize, phase: usize, guard: &Guard) {
+        while Self::is_pending(self.state[tid].load(Ordering::Acquire, guard), phase) {
+            let last = self.tail.load(Ordering::Acquire, guard);
+            let next = unsafe { last.deref() }.next.load(Ordering::Acquire, guard);
+            if last != self.tail.load(Ordering::Acquire, guard) {
+                continue;
+            }
+
+            if !next.is_null() {
+                self.help_finish_enq(guard);
+                continue;
+            }
+
+            let desc = self.state[tid].load(Ordering::Acquire, guard);
+            if Self::is_pending(desc, phase)
+                && unsafe { last.deref() }
+                    .next
+                    .compare_and_set(
+                        Shared::null(),
+                        Shared::from(unsafe { desc.deref() }.node),
+                        Ordering::Release,
+                        guard,
+                    )
+                    .is_ok()
+            {
+                self.help_finish_enq(guard);
+                return;
+            }
+        }
+    }
+
+    /// Finishes the enqueue of the node after `tail`, if any.
+    fn help_finish_enq(&self, guard: &Guard) {
+        let last = self.tail.load(Ordering::Acquire, guard);
+        let next = unsafe { last.deref() }.next.load(Ordering::Acquire, guard);
+        let next_ref = some_or!(unsafe { next.as_ref() }, return);
+
+        let tid = next_ref.enq_tid;
+        let cur = self.state[tid].load(Ordering::Acquire, guard);
+        let cur_ref = some_or!(unsafe { cur.as_ref() }, return);
+        if last == self.tail.load(Ordering::Acquire, guard) && cur_ref.node == next.as_raw() {
+            if cur_ref.pending {
+                let _ = self.update(tid, cur, false, next.as_raw(), guard);
+            }
+            let _ = self
+                .tail
+                .compare_and_set(last, next, Ordering::Release, guard);
+        }
+    }
+
+    fn help_deq(&self, tid: usize, phase: usize, guard: &Guard) {
+        while Self::is_pending(self.state[tid].load(Ordering::Acquire, guard), phase) {
+            let first = self.head.load(Ordering::Acquire, guard);
+            let last = self.tail.load(Ordering::Acquire, guard);
+            let first_ref = unsafe { first.deref() };
+            let next = first_ref.next.load(Ordering::Acquire, guard);
+            if first != self.head.load(Ordering::Acquire, guard) {
+                continue;
+            }
+
+            if first == last {
+                if !next.is_null() {
+                    self.help_finish_enq(guard);
+                    continue;
+                }
+
+                // The queue is empty.
+                let cur = self.state[tid].load(Ordering::Acquire, guard);
+                if last == self.tail.load(Ordering::Acquire, guard) && Self::is_pending(cur, phase)
+                {
+                    let _ = self.update(tid, cur, false, ptr::null(), guard);
+                }
+                continue;
+            }
+
+            let cur = self.state[tid].load(Ordering::Acquire, guard);
+            if !Self::is_pending(cur, phase) {
+                break;
+            }
+
+            // Records `first` in the descriptor, and then reserves `first` for the dequeuer.
+            if first == self.head.load(Ordering::Acquire, guard)
+                && unsafe { cur.deref() }.node != first.as_raw()
+                && !self.update(tid, cur, true, first.as_raw(), guard)
+            {
+                continue;
+            }
+            let _ = first_ref.deq_tid.compare_exchange(
+                NO_TID,
+                tid,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            );
+            self.help_finish_deq(guard);
+        }
+    }
+
+    /// Finishes the dequeue that reserved `head`, if any.
+    fn help_finish_deq(&self, guard: &Guard) {
+        let first = self.head.load(Ordering::Acquire, guard);
+        let first_ref = unsafe { first.deref() };
+        let next = first_ref.next.load(Ordering::Acquire, guard);
+        let tid = first_ref.deq_tid.load(Ordering::Acquire);
+        if tid == NO_TID {
+            return;
+        }
+
+        let cur = self.state[tid].load(Ordering::Acquire, guard);
+        if first == self.head.load(Ordering::Acquire, guard) && !next.is_null() {
+            let cur_ref = unsafe { cur.deref() };
+            if cur_ref.pending {
+                let _ = self.update(tid, cur, false, cur_ref.node, guard);
+            }
+            if self
+                .head
+                .compare_and_set(first, next, Ordering::Release, guard)
+                .is_ok()
+            {
+                unsafe { guard.defer_destroy(first) };
+            }
+        }
+    }
+}
+
+impl<T> Drop for WaitFreeQueue<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+
+            // The first node is the sentinel, and the others contain values.
+            let mut node = self.head.load(Ordering::Relaxed, guard);
+            let mut sentinel = true;
+            while !node.is_null() {
+                let mut owned = node.into_owned();
+                if !sentinel {
+                    ptr::drop_in_place(owned.data.as_mut_ptr());
+                }
+                sentinel = false;
+                node = owned.next.load(Ordering::Relaxed, guard);
+            }
+
+            for desc in self.state.iter() {
+                let desc = desc.load(Ordering::Relaxed, guard);
+                if !desc.is_null() {
+                    drop(desc.into_owned());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_epoch::pin;
+    use crossbeam_utils::thread;
+    use std::sync::Barrier;
+
+    const CONC_COUNT: i64 = 100_000;
+
+    #[test]
+    fn push_try_pop_seq() {
+        let q = WaitFreeQueue::new();
+        let guard = &pin();
+        assert_eq!(q.try_pop(guard), None);
+        for i in 0..200 {
+            q.push(i, guard);
+        }
+        for i in 0..200 {
+            assert_eq!(q.try_pop(guard), Some(i));
+        }
+        assert_eq!(q.try_pop(guard), None);
+    }
+
+    #[test]
+    fn push_try_pop_many_spsc() {
+        let q: WaitFreeQueue<i64> = WaitFreeQueue::new();
+
+        thread::scope(|scope| {
+            scope.spawn(|_| {
+                let mut next = 0;
+
+                while next < CONC_COUNT {
+                    if let Some(elem) = q.try_pop(&pin()) {
+                        assert_eq!(elem, next);
+                        next += 1;
+                    }
+                }
+            });
+
+            for i in 0..CONC_COUNT {
+                q.push(i, &pin())
+            }
+        })
+        .unwrap();
+        assert_eq!(q.try_pop(&pin()), None);
+    }
+
+    #[test]
+    fn push_try_pop_many_mpmc() {
+        const THREADS: i64 = 4;
+        const COUNT: i64 = CONC_COUNT / THREADS;
+
+        let q: WaitFreeQueue<i64> = WaitFreeQueue::new();
+        let popped = thread::scope(|scope| {
+            for t in 0..THREADS {
+                let q = &q;
+                scope.spawn(move |_| {
+                    for i in 0..COUNT {
+                        q.push(t * COUNT + i, &pin());
+                    }
+                });
+            }
+
+            let mut handles = Vec::new();
+            for _ in 0..THREADS {
+                handles.push(scope.spawn(|_| {
+                    let mut last = vec![-1; THREADS as usize];
+                    let mut popped = Vec::new();
+                    for _ in 0..COUNT {
+                        if let Some(elem) = q.try_pop(&pin()) {
+                            // Values from the same producer are popped in order.
+                            let producer = (elem / COUNT) as usize;
+                            assert!(elem > last[producer]);
+                            last[producer] = elem;
+                            popped.push(elem);
+                        }
+                    }
+                    popped
+                }));
+            }
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+
+        let mut all = popped;
+        while let Some(elem) = q.try_pop(&pin()) {
+            all.push(elem);
+        }
+        all.sort_unstable();
+        assert_eq!(all, (0..THREADS * COUNT).collect::<Vec<_>>());
+    }
+
+    /// A thread that announced its push and then got starved is helped by the others: its value is
+    /// enqueued by the time an operation announced later finishes.
+    #[test]
+    fn starved_thread_is_helped() {
+        let q = WaitFreeQueue::new();
+        let announced = Barrier::new(2);
+        let checked = Barrier::new(2);
+
+        let popped = thread::scope(|scope| {
+            scope.spawn(|_| {
+                let guard = &pin();
+                let phase = q.announce_push(1, guard);
+                announced.wait();
+
+                // Starved until the other thread finishes its operations.
+                checked.wait();
+                q.help(phase, guard);
+                q.help_finish_enq(guard);
+            });
+
+            announced.wait();
+            let guard = &pin();
+            q.push(2, guard);
+            let popped = (0..3).map(|_| q.try_pop(guard)).collect::<Vec<_>>();
+            checked.wait();
+            popped
+        })
+        .unwrap();
+
+        // The two pushes are concurrent, so they may be ordered either way.
+        assert!(popped == [Some(1), Some(2), None] || popped == [Some(2), Some(1), None]);
+    }
+
+    #[test]
+    fn thread_ids_are_reused() {
+        let q = WaitFreeQueue::new();
+        thread::scope(|scope| {
+            for i in 0..(2 * MAX_THREADS) {
+                let q = &q;
+                scope.spawn(move |_| q.push(i, &pin())).join().unwrap();
+            }
+        })
+        .unwrap();
+        assert!(registry::high_water() <= MAX_THREADS);
+        for i in 0..(2 * MAX_THREADS) {
+            assert_eq!(q.try_pop(&pin()), Some(i));
+        }
+    }
+}